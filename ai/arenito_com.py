@@ -22,7 +22,7 @@ class ArenitoComms:
         self.log = logger
 
         if mode == AIMode.Simulation:
-            self.connect_simulation(args.filename)
+            self.connect_simulation(args.filename, args.exposure)
         elif mode == AIMode.Jetson:
             self.connect_jetson(args)
         else:
@@ -35,13 +35,21 @@ class ArenitoComms:
 
         self.jetson_interface = JetsonInterface(args, self.log)
 
-    def connect_simulation(self, filename: str):
+    def connect_simulation(self, filename: str, exposure: str):
         """
-        Attaches to simulation's shared memory.
+        Attaches to simulation's shared memory, and sets the cameras'
+        exposure like on the Jetson.
         """
 
         self.sim_interface = SimInterface(filename)
 
+        try:
+            exp = SimInterface.AUTO_EXPOSURE if exposure == 'auto' else int(exposure)
+            self.sim_interface.set_camera_settings(SimInterface.FRONT_CAM, exp)
+            self.sim_interface.set_camera_settings(SimInterface.REAR_CAM, exp)
+        except ValueError:
+            self.log.info(f'Can\'t set exposure_absolute to "{exposure}"')
+
     def get_front_frame(self) -> MatLike:
         """
         Gets the image from the front camera.
@@ -21,13 +21,37 @@ class SimInterface:
     SIM_AKNOWLEDGE_INSTRUCTION = 4
     AI_PROX_SENSOR_READ_REQUEST = 5
     AI_DUMP_CANS = 7
+    AI_HOPPER_COUNT_REQUEST = 8
+    AI_BACKDOOR_SWITCHES_REQUEST = 9
+    AI_RESPAWN_CANS = 10
+    AI_CAMERA_SETTINGS = 11
+    AI_CAMERA_FRAME_REQUEST = 12
+    AI_CAMERA_MOUNT = 13
+    AI_CAMERA_GEOMETRY_REQUEST = 14
+
+    # respawn triggers
+    RESPAWN_MANUAL = 0
+    RESPAWN_NOW = 1
+    RESPAWN_ALL_COLLECTED = 2
+    RESPAWN_PERIODIC = 3
+
+    # respawn placements
+    RESPAWN_ORIGINAL = 0
+    RESPAWN_RANDOM = 1
+
+    # camera ids, as in the sim's camera config
+    FRONT_CAM = 0
+    REAR_CAM = 1
+    # exposure, like v4l2's exposure_absolute (100 µs units)
+    AUTO_EXPOSURE = 0
 
     # memory layout
     SYNC_SIZE = 1
     INCOMING_IMAGE_RES = (512, 512)
     IMAGE_SIZE = 786_432
     SENSOR_COUNT_SIZE = 1
-    MAX_PROXIMITY_SENSOR_COUNT = 7
+    # sensor slots come from the sim's sensor config, up to this many
+    MAX_PROXIMITY_SENSOR_COUNT = 32
 
     def __init__(self, filename: str):
         self.attach(filename)
@@ -74,13 +98,15 @@ class SimInterface:
 
         self.mem[1] = val
 
-    def get_frame(self, instruction: Instruction) -> MatLike:
+    def get_frame(self, camera: int) -> MatLike:
         """
-        Requests a frame and does some processing for the image
-        to be usable by AI.
+        Requests a frame from a camera (by id) and does some processing
+        for the image to be usable by AI.
         """
 
-        self.send_instruction(instruction)
+        self.mem[1] = camera
+        self.set_sync_byte(SimInterface.AI_CAMERA_FRAME_REQUEST)
+        self.wait_confirmation()
 
         raw_img = self.mem[1 : SimInterface.IMAGE_SIZE + 1]
         im = Image.frombytes('RGB', SimInterface.INCOMING_IMAGE_RES, raw_img) # pyright: ignore[reportUnknownMemberType]
@@ -95,14 +121,14 @@ class SimInterface:
         Requests front camera's frame.
         """
 
-        return self.get_frame(Instruction.RequestFrontCam)
+        return self.get_frame(SimInterface.FRONT_CAM)
 
     def get_rear_frame(self) -> MatLike:
         """
         Requests rear camera's frame.
         """
 
-        return self.get_frame(Instruction.RequestRearCam)
+        return self.get_frame(SimInterface.REAR_CAM)
 
     def get_prox_sensors(self) -> list[int]:
         """
@@ -118,6 +144,97 @@ class SimInterface:
 
         return list(self.mem[2 : sensor_count + 2])
 
+    def get_hopper_count(self) -> int:
+        """
+        Returns how many cans are stored in Arenito's hopper.
+        """
+
+        self.set_sync_byte(SimInterface.AI_HOPPER_COUNT_REQUEST)
+        self.wait_confirmation()
+
+        return self.mem[1]
+
+    def get_backdoor_switches(self) -> tuple[bool, bool]:
+        """
+        Returns the backdoor's limit switches (ls_up, ls_down).
+        """
+
+        self.set_sync_byte(SimInterface.AI_BACKDOOR_SWITCHES_REQUEST)
+        self.wait_confirmation()
+
+        return bool(self.mem[1]), bool(self.mem[2])
+
+    def respawn_cans(self):
+        """
+        Respawns cans, following the current respawn placement.
+        """
+
+        self.set_respawn_policy(SimInterface.RESPAWN_NOW)
+
+    def set_respawn_policy(self, trigger: int, placement: int = RESPAWN_ORIGINAL, period: int = 60):
+        """
+        Sets when (trigger) and where (placement) cans are respawned.
+        Period (1 to 255 seconds) is only used by SimInterface.RESPAWN_PERIODIC.
+        """
+
+        self.mem[1] = trigger
+        self.mem[2] = placement
+        self.mem[3] = max(1, min(period, 255))
+        self.set_sync_byte(SimInterface.AI_RESPAWN_CANS)
+        self.wait_confirmation()
+
+    def set_camera_settings(self, camera: int, exposure: int = AUTO_EXPOSURE, gain: float = 1.0, white_balance: tuple[float, float, float] = (1.0, 1.0, 1.0)):
+        """
+        Sets a camera's exposure (in 100 µs units, like v4l2's exposure_absolute),
+        gain and white balance (red, green and blue gains).
+        """
+
+        exposure = max(0, min(exposure, 0xffff))
+        self.mem[1] = camera
+        self.mem[2] = exposure & 0xff
+        self.mem[3] = exposure >> 8
+        self.mem[4] = max(0, min(round(gain * 16), 255))
+        for i, wb in enumerate(white_balance):
+            self.mem[5 + i] = max(0, min(round(wb * 128), 255))
+        self.set_sync_byte(SimInterface.AI_CAMERA_SETTINGS)
+        self.wait_confirmation()
+
+    def point_camera(self, camera: int, pitch: float, yaw: float = 0.0):
+        """
+        Points a camera's pan/tilt mount, angles in degrees: negative pitch
+        looks down, yaw is relative to where the mount faces (positive turns left).
+        Returns once the mount has settled.
+        """
+
+        self.mem[1] = camera
+        for i, angle in enumerate((pitch, yaw)):
+            tenths = max(-0x8000, min(round(angle * 10), 0x7fff)) & 0xffff
+            self.mem[2 + 2 * i] = tenths & 0xff
+            self.mem[3 + 2 * i] = tenths >> 8
+        self.set_sync_byte(SimInterface.AI_CAMERA_MOUNT)
+        self.wait_confirmation()
+
+    def get_camera_geometry(self, camera: int) -> tuple[np.ndarray, np.ndarray, np.ndarray, np.ndarray]:
+        """
+        Requests a camera's geometry, for its frames: returns the camera matrix,
+        its position (cm) and rotation (camera to Arenito) relative to Arenito,
+        and the pixel to ground homography.
+
+        Axes follow OpenCV's (camera looks along z, x right, y down), Arenito's
+        are x forward, y up, z right. A pixel (u, v) sees the ground at
+        (x / w, z / w) cm, with (x, z, w) = homography @ (u, v, 1), if w > 0.
+        """
+
+        self.mem[1] = camera
+        self.set_sync_byte(SimInterface.AI_CAMERA_GEOMETRY_REQUEST)
+        self.wait_confirmation()
+
+        values = np.frombuffer(bytes(self.mem[1 : 25 * 4 + 1]), dtype='<f4')
+        fx, fy, cx, cy = values[:4]
+        camera_matrix = np.array([[fx, 0, cx], [0, fy, cy], [0, 0, 1]])
+
+        return camera_matrix, values[4:7].copy(), values[7:16].reshape(3, 3), values[16:].reshape(3, 3)
+
     def wait_confirmation(self):
         """
         Stalls until sync byte equals SimInterface.SIM_AKNOWLEDGE_INSTRUCTION.
@@ -139,8 +256,6 @@ class SimInterface:
             self.set_sync_byte(SimInterface.AI_PROX_SENSOR_READ_REQUEST)
         elif instr == Instruction.DumpCans:
             raise Exception('Must use proper dump_cans() method!')
-        elif instr == Instruction.ExtendBackdoor:
-            return
         else:
             self.set_sync_byte(SimInterface.AI_MOVE_INSTRUCTION)
             self.set_mov_instruction(ord(INSTRUCTION_MAP[instr]))
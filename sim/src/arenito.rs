@@ -1,13 +1,23 @@
 use crate::{
-    cans::{CanData, CanManager},
+    backdoor::{Backdoor, DoorState},
+    brush::BrushIntake,
+    camera_target::{CameraReadback, CameraTarget, CameraTargetPlugin},
+    cans::{CanData, CanId, CanManager, CanRespawner},
     collision::*,
-    sensor::{AISimMem, ProximitySensor, SimInstruction},
+    collision_shape::{Collider, CollisionShape, HasCollider, Sweep},
+    debris::Debris,
+    deposit::{Deposited, DumpedCan},
+    frame::{Capture, FramePipeline},
+    noise::SensorNoise,
+    sensor::{
+        AISimMem, Infrared, ProximityModel, ProximitySensor, SensorConfig, SensorReport,
+        SimInstruction, Ultrasonic,
+    },
     static_shape::*,
+    stats::EpisodeStats,
+    terrain::Terrain,
 };
-use bevy::{
-    prelude::*,
-    render::view::{screenshot::ScreenshotManager, RenderLayers},
-};
+use bevy::{prelude::*, render::view::RenderLayers, transform::TransformSystem};
 use bevy_obj::*;
 use rand::Rng;
 
@@ -21,6 +31,7 @@ use rand::Rng;
 /// - Arenito spawner startup system
 /// - Arenito's wires startup system
 /// - Arenito mover system
+/// - `CameraTargetPlugin`, Arenito's cameras render to images
 ///
 /// *It also requires that `ObjPlugin` is added.
 pub struct ArenitoPlugin {
@@ -33,21 +44,40 @@ impl Plugin for ArenitoPlugin {
         if !app.is_plugin_added::<ObjPlugin>() {
             app.add_plugins(ObjPlugin);
         }
+        if !app.is_plugin_added::<CameraTargetPlugin>() {
+            app.add_plugins(CameraTargetPlugin);
+        }
 
-        app.insert_resource(self.arenito_config)
+        app.insert_resource(self.arenito_config.clone())
+            .add_event::<ShorelineEvent>()
             .add_systems(Startup, (arenito_spawner, gizmo_config))
             .add_systems(
                 Update,
                 (
                     arenito_ai_mover,
-                    draw_camera_area,
+                    follow_terrain.after(arenito_ai_mover),
+                    (update_camera_area, draw_camera_area)
+                        .chain()
+                        .after(follow_terrain)
+                        .after(operate_camera_mounts),
+                    (detect_shoreline, log_shoreline_events)
+                        .chain()
+                        .after(follow_terrain),
+                    operate_backdoor.after(arenito_ai_mover),
+                    operate_camera_mounts.after(arenito_ai_mover),
+                    export_camera_frames.after(arenito_ai_mover),
                     keyboard_control,
-                    proximity_sensor_reader,
+                    update_hopper_text,
                 ),
+            )
+            .add_systems(
+                PostUpdate,
+                proximity_sensor_reader.after(TransformSystem::TransformPropagate),
             );
 
         if self.enable_can_eating {
-            app.add_systems(Update, eat_cans);
+            // after moving, so pickups test this tick's whole path
+            app.add_systems(Update, (eat_cans, eat_debris).after(follow_terrain));
         }
     }
 }
@@ -61,10 +91,17 @@ fn arenito_spawner(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
     arenito_config: Res<ArenitoConfig>,
 ) {
     let mut arenito = Arenito::new(&arenito_config);
-    arenito.spawn(&mut commands, &mut meshes, &mut materials, &asset_server);
+    arenito.spawn(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &asset_server,
+        &mut images,
+    );
 
     let style = TextStyle {
         font_size: 20.0,
@@ -73,7 +110,12 @@ fn arenito_spawner(
     commands.spawn((
         TextBundle::from_sections([
             TextSection::new(" Mode: ", style.clone()),
-            TextSection::new(format!("{:?}", arenito.control_mode), style),
+            TextSection::new(format!("{:?}", arenito.control_mode), style.clone()),
+            TextSection::new(" Hopper: ", style.clone()),
+            TextSection::new(
+                format!("{}/{}", arenito.hopper, arenito.hopper_capacity),
+                style,
+            ),
         ]),
         ControlText,
     ));
@@ -102,6 +144,9 @@ fn keyboard_control(
         text.sections[1].value = format!("{:?}", arenito.control_mode)
     } else if keyboard_input.just_pressed(KeyCode::R) {
         arenito.reset(&mut arenito_frame.single_mut());
+    } else if keyboard_input.just_pressed(KeyCode::B) && arenito.control_mode == ControlMode::Manual
+    {
+        arenito.brush_on = !arenito.brush_on;
     }
 
     if arenito.control_mode == ControlMode::Manual && arenito.instruction_handler.available() {
@@ -121,7 +166,7 @@ fn keyboard_control(
 fn arenito_ai_mover(
     time: Res<Time>,
     mut aisim: ResMut<AISimMem>,
-    mut screenshot_manager: ResMut<ScreenshotManager>,
+    readback: Res<CameraReadback>,
     mut arenito: Query<&mut Arenito>,
     mut arenito_body: ParamSet<(
         Query<&mut Transform, With<ArenitoCompFrame>>,
@@ -129,13 +174,9 @@ fn arenito_ai_mover(
         Query<&mut Transform, With<ArenitoCompLeftWheel>>,
         Query<&mut Transform, With<ArenitoCompRightWheel>>,
     )>,
-    mut windows: ParamSet<(
-        Query<Entity, With<ArenitoFrontCamWindow>>,
-        Query<Entity, With<ArenitoRearCamWindow>>,
-    )>,
     proximity_sensors: Query<&ProximitySensor>,
-    mut commands: Commands,
-    mut can_manager: ResMut<CanManager>,
+    terrain: Res<Terrain>,
+    mut respawner: ResMut<CanRespawner>,
 ) {
     let mut arenito = arenito.single_mut();
 
@@ -148,43 +189,96 @@ fn arenito_ai_mover(
             HandlerState::Waiting => {
                 if let Some(instr) = aisim.get_instruction() {
                     match instr {
-                        SimInstruction::FrontCamFrame => {
-                            aisim.export_frame(&mut screenshot_manager, &windows.p0().single());
-                            // It's ok to not await this frame, since it's probably very similar to previous.
-                            // Awaiting every frame makes the robot sluggish, so I chose not to wait for forward frames.
-                            // aisim.confirm_instruction();
-                        }
-                        SimInstruction::RearCamFrame => {
-                            aisim.export_frame(&mut screenshot_manager, &windows.p1().single());
-                            // But for rear cam, it's very important to make sure the image is from this camera.
+                        SimInstruction::CameraFrame(id) => {
+                            let id = id as usize;
+                            match arenito.cameras.get(id) {
+                                Some(cam) => {
+                                    let capture = arenito.capture(cam, arenito_body.p0().single());
+                                    // confirmed once the frame's read back,
+                                    // see `export_camera_frames`
+                                    arenito.frame_requests.push((id, capture));
+                                    readback.request(id);
+                                    aisim.hold_instruction();
+                                }
+                                None => {
+                                    println!("Unrecognized camera '{}'", id);
+                                    aisim.confirm_instruction();
+                                }
+                            }
                         }
                         SimInstruction::ProxSensorReads => {
-                            let mut sensor_reads = vec![0_u8; AISimMem::MAX_PROXIMITY_SENSOR_COUNT];
-                            for sensor in proximity_sensors.iter() {
-                                sensor_reads[sensor.index] = (sensor.range * 33.0) as u8;
-                            }
-                            // mock "real" sensor setup
-                            // first two are rear ultrasonics
-                            // then infrared front left, middle, right
-                            // lastly infrared rear left, right
-                            // then rear left, right
-                            // sim setup is: rear left, right then front left, middle
-                            for i in 2..AISimMem::MAX_PROXIMITY_SENSOR_COUNT {
-                                sensor_reads[i] = (sensor_reads[i] < 50) as u8;
-                            }
-                            sensor_reads[5] = (sensor_reads[0] < 8) as u8;
-                            sensor_reads[6] = (sensor_reads[1] < 8) as u8;
-                            aisim.export_sensor_reads(sensor_reads);
+                            aisim.export_sensor_reads(ProximitySensor::report(
+                                proximity_sensors.iter(),
+                                arenito.sensor_report,
+                            ));
                         }
                         SimInstruction::DumpCans(n) => {
-                            dump_cans(
-                                &mut commands,
-                                &mut can_manager,
-                                arenito_body.p0().single(),
-                                n,
-                            );
+                            // cans are dumped once the backdoor is open,
+                            // see `operate_backdoor`
+                            arenito.backdoor.open();
+                            arenito.backdoor_task = Some(BackdoorTask::DumpCans(n));
+                            aisim.hold_instruction();
+                        }
+                        SimInstruction::ExtendBackdoor => {
+                            arenito.backdoor.extend();
+                            arenito.backdoor_task = Some(BackdoorTask::Extend);
+                            aisim.hold_instruction();
+                        }
+                        SimInstruction::HopperCount => {
+                            aisim.export_hopper_count(arenito.hopper);
+                        }
+                        SimInstruction::BackdoorSwitches => {
+                            aisim.export_backdoor_switches(arenito.backdoor.limit_switches());
+                        }
+                        SimInstruction::RespawnCans => {
+                            respawner.request();
+                            aisim.confirm_instruction();
+                        }
+                        SimInstruction::SetRespawnPolicy(policy) => {
+                            respawner.set_policy(policy);
+                            aisim.confirm_instruction();
+                        }
+                        SimInstruction::Ignored => {
+                            aisim.confirm_instruction();
+                        }
+                        SimInstruction::SetCameraSettings(camera, settings) => {
+                            match arenito.cameras.get_mut(camera as usize) {
+                                Some(cam) => cam.settings = settings,
+                                None => println!("Unrecognized camera '{}'", camera),
+                            }
                             aisim.confirm_instruction();
                         }
+                        SimInstruction::CameraGeometry(id) => {
+                            match arenito.cameras.get(id as usize) {
+                                Some(cam) => {
+                                    let geometry = cam.geometry(
+                                        arenito_body.p0().single(),
+                                        &terrain,
+                                        AISimMem::FRAME_SIZE as f32,
+                                    );
+                                    aisim.export_camera_geometry(&geometry);
+                                }
+                                None => {
+                                    println!("Unrecognized camera '{}'", id);
+                                    aisim.confirm_instruction();
+                                }
+                            }
+                        }
+                        SimInstruction::PointCamera { camera, pitch, yaw } => {
+                            match arenito.cameras.get_mut(camera as usize) {
+                                Some(cam) => {
+                                    cam.mount.point(pitch, yaw);
+                                    // confirmed once the mount settles,
+                                    // see `operate_camera_mounts`
+                                    arenito.mount_task = Some(camera as usize);
+                                    aisim.hold_instruction();
+                                }
+                                None => {
+                                    println!("Unrecognized camera '{}'", camera);
+                                    aisim.confirm_instruction();
+                                }
+                            }
+                        }
                         SimInstruction::BrushOn => {
                             arenito.brush_on = true;
                             aisim.confirm_instruction();
@@ -204,34 +298,127 @@ fn arenito_ai_mover(
         }
     }
 
-    arenito.update(time.delta().as_millis(), arenito_body);
+    arenito.update(time.delta().as_millis(), &terrain, arenito_body);
+}
+
+/// Moves the cameras' pan/tilt mounts, and lets the AI know once
+/// the camera it pointed has settled.
+fn operate_camera_mounts(
+    time: Res<Time>,
+    mut aisim: ResMut<AISimMem>,
+    mut arenito: Query<&mut Arenito>,
+    mut cameras: Query<(&CameraTarget, &mut Transform)>,
+    mut models: Query<(&CameraModel, &mut Transform), Without<CameraTarget>>,
+) {
+    let mut arenito = arenito.single_mut();
+    for cam in arenito.cameras.iter_mut() {
+        cam.mount.update(time.delta_seconds());
+    }
+
+    for (target, mut transform) in cameras.iter_mut() {
+        if let Some(cam) = arenito.cameras.get(target.id) {
+            *transform = cam.camera_transform();
+        }
+    }
+    for (model, mut transform) in models.iter_mut() {
+        if let Some(cam) = arenito.cameras.get(model.0) {
+            *transform = cam.model_transform();
+        }
+    }
+
+    if let Some(id) = arenito.mount_task {
+        if arenito
+            .cameras
+            .get(id)
+            .is_none_or(|cam| cam.mount.is_settled())
+        {
+            arenito.mount_task = None;
+            aisim.confirm_instruction();
+        }
+    }
+}
+
+/// Writes the camera frames requested by the AI, once they're read back.
+fn export_camera_frames(
+    mut aisim: ResMut<AISimMem>,
+    readback: Res<CameraReadback>,
+    mut arenito: Query<&mut Arenito>,
+) {
+    let mut arenito = arenito.single_mut();
+
+    for frame in readback.receive() {
+        let Some(i) = arenito
+            .frame_requests
+            .iter()
+            .position(|(id, _)| *id == frame.id)
+        else {
+            continue;
+        };
+        let (_, capture) = arenito.frame_requests.remove(i);
+        aisim.export_frame(frame.image, arenito.frame_pipeline(), capture);
+    }
+}
+
+/// Moves the backdoor and finishes the backdoor tasks requested by the AI.
+/// Cans only leave the hopper once the door is open (`ls_up` pressed),
+/// and the AI gets its confirmation once the door is closed again.
+fn operate_backdoor(
+    time: Res<Time>,
+    mut aisim: ResMut<AISimMem>,
+    mut commands: Commands,
+    mut can_manager: ResMut<CanManager>,
+    terrain: Res<Terrain>,
+    mut arenito: Query<(&mut Arenito, &Transform)>,
+    mut backdoor_parts: Query<(&ArenitoCompBackdoor, &mut Transform), Without<Arenito>>,
+) {
+    let (mut arenito, transform) = arenito.single_mut();
+    arenito.backdoor.update(time.delta_seconds());
+
+    match arenito.backdoor_task {
+        Some(BackdoorTask::DumpCans(n)) if arenito.backdoor.is_open() => {
+            let n = arenito.release_cans(n);
+            dump_cans(&mut commands, &mut can_manager, transform, &terrain, n);
+            arenito.backdoor.close();
+            arenito.backdoor_task = Some(BackdoorTask::Close);
+        }
+        Some(BackdoorTask::Close) if arenito.backdoor.state() == DoorState::Closed => {
+            arenito.backdoor_task = None;
+            aisim.confirm_instruction();
+        }
+        Some(BackdoorTask::Extend) if arenito.backdoor.is_retracted() => {
+            arenito.backdoor_task = None;
+            aisim.confirm_instruction();
+        }
+        _ => {}
+    }
+
+    for (part, mut part_transform) in backdoor_parts.iter_mut() {
+        match part {
+            ArenitoCompBackdoor::Hinge => {
+                part_transform.rotation = Quat::from_rotation_z(-arenito.backdoor.angle());
+            }
+            ArenitoCompBackdoor::Extension => {
+                part_transform.translation.y =
+                    Arenito::BACKDOOR_EXT_Y - arenito.backdoor.extension_offset();
+            }
+        }
+    }
 }
 
 /// Currently, Arenito reacts immediately if the distance read by the single sensor
 /// is lower than the minimum activation range.
+/// Runs after transform propagation, so sensors are read from where
+/// Arenito is this frame.
 fn proximity_sensor_reader(
-    arenito_transform: Query<&Transform, With<Arenito>>,
-    obstacles: Query<(&Obstacle, &Handle<Mesh>, &Transform)>,
-    mut proxs: Query<(&mut ProximitySensor, &Transform)>,
-    meshes: Res<Assets<Mesh>>,
+    obstacle_grid: Res<ObstacleGrid>,
+    terrain: Res<Terrain>,
+    mut proxs: Query<(&mut ProximitySensor, &GlobalTransform)>,
     mut gizmos: Gizmos,
 ) {
-    let arenito_transform = arenito_transform.single();
-    let obstacle_hulls: Vec<Vec<Triangle>> = obstacles
-        .iter()
-        .map(|(obstacle, mesh_handle, transform)| {
-            let mesh = meshes.get(mesh_handle).unwrap();
-            obstacle.compute_hull(mesh, transform)
-        })
-        .collect();
+    let mut rng = rand::thread_rng();
 
     for (mut prox, prox_transform) in proxs.iter_mut() {
-        prox.reset();
-        let prox_transform = prox_transform.from_parent(&arenito_transform);
-
-        for hull in obstacle_hulls.iter() {
-            prox.collides_with_mesh(&prox_transform, hull);
-        }
+        prox.read(prox_transform, &obstacle_grid, &terrain, &mut rng);
 
         // const ACTIVATION_RANGE: f32 = 1.5;
 
@@ -239,18 +426,95 @@ fn proximity_sensor_reader(
         //     arenito.instruction_handler.set(SimInstruction::Evade);
         // }
 
-        prox.draw_ray(&prox_transform, &mut gizmos);
+        prox.draw_ray(prox_transform, &mut gizmos);
+    }
+}
+
+/// Places Arenito on the ground, so that its height, pitch and roll follow
+/// the terrain below its wheels.
+fn follow_terrain(
+    terrain: Res<Terrain>,
+    mut arenito_frame: Query<&mut Transform, With<ArenitoCompFrame>>,
+) {
+    let mut arenito_frame = arenito_frame.single_mut();
+    Arenito::follow_terrain(&mut arenito_frame, &terrain);
+}
+
+/// Fired when Arenito crosses the shoreline.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub enum ShorelineEvent {
+    /// At least one wheel got off the sand.
+    LeftSand,
+    /// Arenito's center got into the water.
+    EnteredWater,
+}
+
+/// Checks whether Arenito crossed the shoreline since the last frame.
+/// `state` remembers whether Arenito was (partially) off the sand and
+/// in the water, so each event fires once per crossing.
+fn detect_shoreline(
+    terrain: Res<Terrain>,
+    arenito_frame: Query<&Transform, With<ArenitoCompFrame>>,
+    mut state: Local<(bool, bool)>,
+    mut events: EventWriter<ShorelineEvent>,
+) {
+    let transform = arenito_frame.single();
+    let (was_off_sand, was_in_water) = *state;
+
+    let off_sand = [(WOX, WOZ), (WOX, -WOZ), (-WOX, WOZ), (-WOX, -WOZ)]
+        .iter()
+        .any(|(x, z)| {
+            let p = transform.transform_point(Vec3::new(*x, WOY, *z));
+            terrain.is_water(p.x, p.z)
+        });
+    let in_water = terrain.is_water(transform.translation.x, transform.translation.z);
+
+    if off_sand && !was_off_sand {
+        events.send(ShorelineEvent::LeftSand);
+    }
+    if in_water && !was_in_water {
+        events.send(ShorelineEvent::EnteredWater);
+    }
+
+    *state = (off_sand, in_water);
+}
+
+fn log_shoreline_events(mut events: EventReader<ShorelineEvent>) {
+    for event in events.read() {
+        match event {
+            ShorelineEvent::LeftSand => println!("Arenito left the sand!"),
+            ShorelineEvent::EnteredWater => println!("Arenito entered the water!"),
+        }
+    }
+}
+
+/// Recomputes the cameras' visible area and glare, since they depend on
+/// the ground Arenito is on.
+fn update_camera_area(
+    mut arenito: Query<(&mut Arenito, &Transform)>,
+    terrain: Res<Terrain>,
+    lights: Query<(&DirectionalLight, &GlobalTransform)>,
+) {
+    let (mut arenito, transform) = arenito.single_mut();
+    let arenito = &mut *arenito;
+    // (illuminance, direction towards the light)
+    let sunlight: Vec<(f32, Vec3)> = lights
+        .iter()
+        .map(|(light, transform)| (light.illuminance, -transform.forward()))
+        .collect();
+
+    for cam in arenito.cameras.iter_mut() {
+        cam.compute_area(transform, &terrain);
+        cam.glare = Capture::glare(cam.view_dir(transform), sunlight.iter().copied());
     }
 }
 
 fn draw_camera_area(arenito: Query<(&Arenito, &Transform)>, mut gizmos: Gizmos) {
     fn draw_area(points: Vec<Vec3>, transform: &Transform, gizmos: &mut Gizmos) {
         let mut points = points;
-        let rot = transform.rotation;
-        let pos = transform.translation;
 
         for i in 0..points.len() {
-            points[i] = rot.mul_vec3(points[i]) + Vec3::new(pos.x, 0.0, pos.z);
+            points[i] = transform.transform_point(points[i]);
         }
 
         for i in 0..points.len() - 1 {
@@ -260,12 +524,9 @@ fn draw_camera_area(arenito: Query<(&Arenito, &Transform)>, mut gizmos: Gizmos)
     }
 
     let (arenito, transform) = arenito.single();
-    draw_area(
-        arenito.front_cam_data.points.clone(),
-        transform,
-        &mut gizmos,
-    );
-    draw_area(arenito.rear_cam_data.points.clone(), transform, &mut gizmos);
+    for cam in arenito.cameras.iter() {
+        draw_area(cam.points.clone(), transform, &mut gizmos);
+    }
 
     // This should not be here
     //arenito.draw_sphere(transform, Color::WHITE, &mut gizmos);
@@ -391,12 +652,27 @@ impl Default for InstructionHandler {
     }
 }
 
+/// Backdoor operation the AI is waiting on.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum BackdoorTask {
+    DumpCans(u8),
+    Close,
+    Extend,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 enum ControlMode {
     Manual,
     AI,
 }
 
+// wheel offsets, relative to Arenito's center
+const WOX: f32 = 0.5;
+const WOY: f32 = -0.2;
+const WOZ: f32 = 0.85;
+// height of Arenito's center over the ground
+const RIDE_HEIGHT: f32 = 0.2;
+
 #[derive(Component)]
 pub struct ArenitoCompFrame;
 
@@ -409,21 +685,98 @@ pub struct ArenitoCompRightWheel;
 #[derive(Component)]
 pub struct ArenitoCompBrush;
 
-#[derive(Component, Copy, Clone)]
-pub struct ArenitoFrontCamWindow;
-
-#[derive(Component, Copy, Clone)]
-pub struct ArenitoRearCamWindow;
+#[derive(Component)]
+pub enum ArenitoCompBackdoor {
+    Hinge,
+    Extension,
+}
 
-#[derive(Resource, Copy, Clone)]
+#[derive(Resource, Clone)]
 pub struct ArenitoConfig {
     pub initial_pos: Transform,
     pub brush_speed: f32,
     pub velocity_k: f32,
     pub visible_cameras: bool,
+    pub hopper_capacity: u8,
+    /// Onboard cameras, their id is their index.
+    pub cameras: Vec<CameraData>,
+    pub proximity_sensors: Vec<SensorConfig>,
+    pub sensor_report: SensorReport,
+    /// Ignore every sensor's noise, handy when debugging the AI's logic.
+    pub ideal_sensors: bool,
+    /// How exported camera frames are degraded.
+    pub frame_pipeline: FramePipeline,
 }
 
 impl ArenitoConfig {
+    /// Proximity sensors, as in the real setup.
+    /// Rear sensors are mounted low, so they see cans and the deposit's lip,
+    /// front sensors look over them.
+    pub fn proximity_sensors() -> Vec<SensorConfig> {
+        let front_rot = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, -15.0_f32.to_radians());
+        let rear_rot = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, std::f32::consts::PI);
+        let ultrasonic = ProximityModel::Ultrasonic(Ultrasonic::default());
+        let front_ir = ProximityModel::Infrared(Infrared::new(1.5));
+        let rear_ir = ProximityModel::Infrared(Infrared::new(0.24));
+
+        [
+            (
+                "rear_us_left",
+                Vec3::new(-0.64, -0.03, 0.5),
+                rear_rot,
+                ultrasonic,
+            ),
+            (
+                "rear_us_right",
+                Vec3::new(-0.64, -0.03, -0.5),
+                rear_rot,
+                ultrasonic,
+            ),
+            (
+                "front_ir_left",
+                Vec3::new(0.74, 1.3, 0.5),
+                front_rot,
+                front_ir,
+            ),
+            (
+                "front_ir_middle",
+                Vec3::new(0.74, 1.4, 0.0),
+                front_rot,
+                front_ir,
+            ),
+            (
+                "front_ir_right",
+                Vec3::new(0.74, 1.3, -0.5),
+                front_rot,
+                front_ir,
+            ),
+            (
+                "rear_ir_left",
+                Vec3::new(-0.64, -0.03, 0.35),
+                rear_rot,
+                rear_ir,
+            ),
+            (
+                "rear_ir_right",
+                Vec3::new(-0.64, -0.03, -0.35),
+                rear_rot,
+                rear_ir,
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(slot, (name, pos, rot, model))| SensorConfig {
+            name,
+            offset: Transform::from_translation(pos).with_rotation(rot),
+            model,
+            slot,
+            encoding: model.native_encoding(),
+            layers: model.default_layers(),
+            noise: model.default_noise(),
+        })
+        .collect()
+    }
+
     pub fn deposit_test(visible_cameras: bool) -> Self {
         ArenitoConfig {
             initial_pos: Transform::from_xyz(-3.0, 0.2, 3.0).with_rotation(Quat::from_euler(
@@ -450,6 +803,12 @@ impl Default for ArenitoConfig {
             brush_speed: 10.0,
             velocity_k: 1.5,
             visible_cameras: false,
+            hopper_capacity: 12,
+            cameras: vec![CameraData::front(), CameraData::rear()],
+            proximity_sensors: Self::proximity_sensors(),
+            sensor_report: SensorReport::Firmware,
+            ideal_sensors: false,
+            frame_pipeline: FramePipeline::clean(),
         }
     }
 }
@@ -463,49 +822,127 @@ impl Default for ArenitoConfig {
 pub struct Arenito {
     pub vel: Vec3,
     pub acc: Vec3,
-    front_cam_data: CameraData,
-    rear_cam_data: CameraData,
+    pub yaw_rate: f32, // rad/s, positive turning left
+    cameras: Vec<CameraData>,
     initial_pos: Transform,
+    last_transform: Transform, // where this tick's motion started
     brush_speed: f32,
     velocity_k: f32,
     brush_offset: Vec3, // brush pos relative to Arenito's center
     brush_on: bool,
+    brush: BrushIntake,
     instruction_handler: InstructionHandler,
     control_mode: ControlMode,
-    proximity_sensor_offsets: Vec<Transform>,
+    proximity_sensors: Vec<SensorConfig>,
+    sensor_report: SensorReport,
+    ideal_sensors: bool,
+    frame_pipeline: FramePipeline,
+    frame_requests: Vec<(usize, Capture)>, // frames waiting for readback
     visible_cameras: bool,
+    hopper: u8, // cans on board
+    hopper_capacity: u8,
+    backdoor: Backdoor,
+    backdoor_task: Option<BackdoorTask>,
+    mount_task: Option<usize>, // camera waiting for its mount to settle
 }
 
 impl Arenito {
+    // backdoor hinge pos relative to Arenito's center
+    const BACKDOOR_HINGE: Vec3 = Vec3::new(-0.72, 1.3, 0.0);
+    const BACKDOOR_HEIGHT: f32 = 1.4;
+    // extension's height relative to the backdoor's center, when retracted
+    const BACKDOOR_EXT_Y: f32 = -0.55;
+    // brush pos relative to Arenito's center
+    const BRUSH_OFFSET: Vec3 = Vec3::new(0.75, 0.4, 0.0);
+
     /// Returns an empty, non-spawned Arenito.
     pub fn new(config: &ArenitoConfig) -> Self {
-        let front_sensor_rot = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, -15.0_f32.to_radians());
-        let rear_sensor_rot = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, std::f32::consts::PI);
         Arenito {
             vel: Vec3::ZERO,
             acc: Vec3::ZERO,
-            front_cam_data: CameraData::front(),
-            rear_cam_data: CameraData::rear(),
-            brush_offset: Vec3::new(0.75, 0.4, 0.0),
+            yaw_rate: 0.0,
+            cameras: config.cameras.clone(),
+            brush_offset: Self::BRUSH_OFFSET,
             brush_on: false,
+            // the capture zone reaches down to the ground, for flat stuff
+            brush: BrushIntake {
+                height: Self::BRUSH_OFFSET.y + RIDE_HEIGHT,
+                ..default()
+            },
             instruction_handler: InstructionHandler::default(),
             control_mode: ControlMode::AI,
-            proximity_sensor_offsets: vec![
-                // rear
-                Transform::from_xyz(-0.64, -0.03, 0.5).with_rotation(rear_sensor_rot),
-                Transform::from_xyz(-0.64, -0.03, -0.5).with_rotation(rear_sensor_rot),
-                // front
-                Transform::from_xyz(0.74, 1.3, 0.5).with_rotation(front_sensor_rot),
-                Transform::from_xyz(0.74, 1.4, 0.0).with_rotation(front_sensor_rot),
-                Transform::from_xyz(0.74, 1.3, -0.5).with_rotation(front_sensor_rot),
-            ],
+            proximity_sensors: config.proximity_sensors.clone(),
+            sensor_report: config.sensor_report,
+            ideal_sensors: config.ideal_sensors,
+            frame_pipeline: config.frame_pipeline.clone(),
+            frame_requests: Vec::new(),
             brush_speed: config.brush_speed,
             initial_pos: config.initial_pos,
+            last_transform: config.initial_pos,
             velocity_k: config.velocity_k,
             visible_cameras: config.visible_cameras,
+            hopper: 0,
+            hopper_capacity: config.hopper_capacity,
+            backdoor: Backdoor::new(),
+            backdoor_task: None,
+            mount_task: None,
+        }
+    }
+
+    /// Returns what `cam` is doing right now, given Arenito's transform.
+    pub fn capture(&self, cam: &CameraData, transform: &Transform) -> Capture {
+        let view = cam.view_dir(transform);
+        Capture {
+            focal: cam.focal_length(AISimMem::FRAME_SIZE as f32),
+            forward_speed: self.vel.dot(view),
+            yaw_rate: self.yaw_rate,
+            settings: cam.settings,
+            glare: cam.glare,
         }
     }
 
+    /// Returns how exported frames are degraded (clean, with ideal sensors).
+    pub fn frame_pipeline(&self) -> FramePipeline {
+        if self.ideal_sensors {
+            FramePipeline::clean()
+        } else {
+            self.frame_pipeline.clone()
+        }
+    }
+
+    /// Returns the brush's transform, given Arenito's.
+    pub fn brush_transform(&self, transform: &Transform) -> Transform {
+        transform.mul_transform(Transform::from_translation(self.brush_offset))
+    }
+
+    /// Returns the brush's capture zone along this tick's motion
+    /// (from where Arenito started moving to `transform`).
+    pub fn brush_sweep(&self, transform: &Transform) -> Sweep {
+        self.brush.capture_zone().sweep(
+            &self.brush_transform(&self.last_transform).into(),
+            &self.brush_transform(transform).into(),
+        )
+    }
+
+    /// Stores a can in the hopper.
+    /// Returns false if there's no room left.
+    pub fn store_can(&mut self) -> bool {
+        if self.hopper >= self.hopper_capacity {
+            return false;
+        }
+
+        self.hopper += 1;
+        true
+    }
+
+    /// Takes at most `cans` out of the hopper.
+    /// Returns how many cans were actually released.
+    pub fn release_cans(&mut self, cans: u8) -> u8 {
+        let released = cans.min(self.hopper);
+        self.hopper -= released;
+        released
+    }
+
     /// Spawns Arenito (body cube and wheels) into the scene.
     ///
     /// Arenito's model is a cube (parent) with four wheels (children).
@@ -518,10 +955,9 @@ impl Arenito {
         meshes: &mut ResMut<Assets<Mesh>>,
         materials: &mut ResMut<Assets<StandardMaterial>>,
         asset_server: &Res<AssetServer>,
+        images: &mut Assets<Image>,
     ) {
         const CENTER: Vec3 = Vec3::new(0.0, 0.2, 0.0);
-        self.front_cam_data.compute_area(CENTER.y);
-        self.rear_cam_data.compute_area(CENTER.y);
 
         commands
             .spawn((
@@ -535,10 +971,6 @@ impl Arenito {
                 self.clone(),
             ))
             .with_children(|parent| {
-                const WOX: f32 = 0.5;
-                const WOY: f32 = -0.2;
-                const WOZ: f32 = 0.85;
-
                 let rwheel_offsets = [Vec3::new(WOX, WOY, WOZ), Vec3::new(-WOX, WOY, WOZ)];
                 let lwheel_offsets = [Vec3::new(WOX, WOY, -WOZ), Vec3::new(-WOX, WOY, -WOZ)];
 
@@ -576,19 +1008,25 @@ impl Arenito {
                 let sensor_mesh = meshes.add(shape::Cube::new(0.08).into());
                 let sensor_material = materials.add(Color::rgb(0.3, 0.3, 0.6).into());
 
-                for (i, prox_offset) in self.proximity_sensor_offsets.iter().enumerate() {
+                for sensor in self.proximity_sensors.iter() {
+                    let mut proximity_sensor = ProximitySensor::from_config(sensor);
+                    if self.ideal_sensors {
+                        proximity_sensor.noise = SensorNoise::ideal();
+                    }
+
                     parent.spawn(PbrBundle {
-                        transform: *prox_offset,
+                        transform: sensor.offset,
                         mesh: sensor_mesh.clone(),
                         material: sensor_material.clone(),
                         ..default()
                     });
                     parent.spawn((
                         PbrBundle {
-                            transform: *prox_offset,
+                            transform: sensor.offset,
                             ..default()
                         },
-                        ProximitySensor::default().set_index(i),
+                        proximity_sensor,
+                        Name::new(sensor.name),
                     ));
                 }
 
@@ -615,23 +1053,54 @@ impl Arenito {
                     ..default()
                 });
 
-                self.front_cam_data.spawn(
-                    parent,
-                    materials,
-                    asset_server,
-                    &ArenitoFrontCamWindow,
-                    "Front view".to_owned(),
-                    self.visible_cameras,
-                );
+                // backdoor, hanging from its hinge
+                parent
+                    .spawn((
+                        SpatialBundle::from_transform(Transform::from_translation(
+                            Self::BACKDOOR_HINGE,
+                        )),
+                        ArenitoCompBackdoor::Hinge,
+                    ))
+                    .with_children(|hinge| {
+                        hinge
+                            .spawn(PbrBundle {
+                                mesh: meshes
+                                    .add(shape::Box::new(0.04, Self::BACKDOOR_HEIGHT, 1.3).into()),
+                                material: materials.add(Color::GRAY.into()),
+                                transform: Transform::from_xyz(
+                                    0.0,
+                                    -Self::BACKDOOR_HEIGHT / 2.0,
+                                    0.0,
+                                ),
+                                ..default()
+                            })
+                            .with_children(|door| {
+                                door.spawn((
+                                    PbrBundle {
+                                        mesh: meshes.add(shape::Box::new(0.03, 0.3, 1.2).into()),
+                                        material: materials.add(Color::DARK_GRAY.into()),
+                                        transform: Transform::from_xyz(
+                                            0.0,
+                                            Self::BACKDOOR_EXT_Y,
+                                            0.0,
+                                        ),
+                                        ..default()
+                                    },
+                                    ArenitoCompBackdoor::Extension,
+                                ));
+                            });
+                    });
 
-                self.rear_cam_data.spawn(
-                    parent,
-                    materials,
-                    asset_server,
-                    &ArenitoRearCamWindow,
-                    "Rear view".to_owned(),
-                    self.visible_cameras,
-                );
+                for (id, cam) in self.cameras.iter().enumerate() {
+                    cam.spawn(
+                        parent,
+                        materials,
+                        asset_server,
+                        images,
+                        id,
+                        self.visible_cameras,
+                    );
+                }
             });
     }
 
@@ -641,10 +1110,13 @@ impl Arenito {
     pub fn reset(&mut self, arenito_frame: &mut Transform) {
         self.acc = Vec3::ZERO;
         self.vel = Vec3::ZERO;
+        self.yaw_rate = 0.0;
         self.instruction_handler.reset();
 
         arenito_frame.translation = self.initial_pos.translation;
         arenito_frame.rotation = self.initial_pos.rotation;
+        // teleported, not swept
+        self.last_transform = *arenito_frame;
     }
 
     /// Applies the movement given some delta time.
@@ -658,10 +1130,12 @@ impl Arenito {
     /// It also updates Arenito's velocity and acceleration.
     ///
     /// * `delta_ms` - time delta between this and the last frame this was called.
+    /// * `terrain` - the ground Arenito moves on.
     /// * `arenito3d` - Bevy's way of finding elements.
     pub fn update(
         &mut self,
         delta_ms: u128,
+        terrain: &Terrain,
         mut arenito_body: ParamSet<(
             Query<&mut Transform, With<ArenitoCompFrame>>,
             Query<&mut Transform, With<ArenitoCompBrush>>,
@@ -670,50 +1144,108 @@ impl Arenito {
         )>,
     ) {
         let delta = delta_ms as f32 / 1000.0;
-        let (pos, rot) = self.update_pos(delta, arenito_body.p0().single());
+        self.last_transform = *arenito_body.p0().single();
+        let (pos, rot) = self.update_pos(delta, arenito_body.p0().single(), terrain);
+        if delta > 0.0 {
+            self.vel = pos / delta;
+            let (axis, angle) = rot.to_axis_angle();
+            self.yaw_rate = axis.y * angle / delta;
+        }
 
         self.update_model(pos, rot, delta, arenito_body);
     }
 
+    /// Sets Arenito's height, pitch and roll according to the terrain
+    /// under its wheels. Arenito's heading (yaw) is preserved.
+    pub fn follow_terrain(transform: &mut Transform, terrain: &Terrain) {
+        let forward = transform.rotation.mul_vec3(Vec3::X);
+        let yaw = Quat::from_rotation_y((-forward.z).atan2(forward.x));
+        let pos = transform.translation;
+
+        let ground = |x: f32, z: f32| {
+            let p = yaw.mul_vec3(Vec3::new(x, 0.0, z)) + pos;
+            terrain.height_at(p.x, p.z)
+        };
+
+        let front_right = ground(WOX, WOZ);
+        let front_left = ground(WOX, -WOZ);
+        let rear_right = ground(-WOX, WOZ);
+        let rear_left = ground(-WOX, -WOZ);
+
+        let pitch = ((front_right + front_left) - (rear_right + rear_left)).atan2(4.0 * WOX);
+        let roll = ((front_left + rear_left) - (front_right + rear_right)).atan2(4.0 * WOZ);
+
+        transform.translation.y =
+            (front_right + front_left + rear_right + rear_left) / 4.0 + RIDE_HEIGHT;
+        transform.rotation = yaw * Quat::from_rotation_z(pitch) * Quat::from_rotation_x(roll);
+    }
+
+    /// Returns the traction of Arenito's left and right sides, given the
+    /// ground material under each wheel.
+    fn side_traction(transform: &Transform, terrain: &Terrain) -> (f32, f32) {
+        let traction = |x: f32, z: f32| {
+            let p = transform.transform_point(Vec3::new(x, WOY, z));
+            terrain.material_at(p.x, p.z).traction()
+        };
+
+        (
+            (traction(WOX, -WOZ) + traction(-WOX, -WOZ)) / 2.0,
+            (traction(WOX, WOZ) + traction(-WOX, WOZ)) / 2.0,
+        )
+    }
+
     /// Calculates position difference after executing `instruction`.
+    ///
+    /// Arenito is moved like a differential drive: each instruction sets
+    /// the direction of the left and right wheels, and each side's speed
+    /// is scaled by its traction (`side_traction`). When one side
+    /// slips more than the other, Arenito drifts towards it.
     fn calculate_next_pos(
         &self,
         transform: &Transform,
         instruction: BaseInstruction,
+        traction: (f32, f32),
         time: f32,
     ) -> (Vec3, Quat) {
-        match instruction {
-            BaseInstruction::Back => (
-                transform.rotation.mul_vec3(Vec3::X) * self.velocity_k * -1.0 * time,
-                Quat::IDENTITY,
-            ),
-            BaseInstruction::Forward => (
-                transform.rotation.mul_vec3(Vec3::X) * self.velocity_k * time,
-                Quat::IDENTITY,
-            ),
-            BaseInstruction::Left => (
-                Vec3::ZERO,
-                Quat::from_euler(EulerRot::XYZ, 0.0, self.velocity_k * time, 0.0),
-            ),
-            BaseInstruction::Right => (
-                Vec3::ZERO,
-                Quat::from_euler(EulerRot::XYZ, 0.0, -self.velocity_k * time, 0.0),
-            ),
-        }
+        let (l, r) = match instruction {
+            BaseInstruction::Back => (-1.0, -1.0),
+            BaseInstruction::Forward => (1.0, 1.0),
+            BaseInstruction::Left => (-1.0, 1.0),
+            BaseInstruction::Right => (1.0, -1.0),
+        };
+        let vl = l * traction.0 * self.velocity_k;
+        let vr = r * traction.1 * self.velocity_k;
+
+        let advance = (vl + vr) / 2.0 * time;
+        let turn = (vr - vl) / 2.0 * time;
+
+        let pos = if advance == 0.0 {
+            Vec3::ZERO
+        } else {
+            transform.rotation.mul_vec3(Vec3::X) * advance
+        };
+        let rot = if turn == 0.0 {
+            Quat::IDENTITY
+        } else {
+            Quat::from_euler(EulerRot::XYZ, 0.0, turn, 0.0)
+        };
+
+        (pos, rot)
     }
 
     /// Updates Arenito's position given some time in seconds (`delta`).
     /// This method is suposed to be called every frame, where delta
     /// is the time between this frame's render and the previous.
-    fn update_pos(&mut self, delta: f32, transform: &Transform) -> (Vec3, Quat) {
+    fn update_pos(&mut self, delta: f32, transform: &Transform, terrain: &Terrain) -> (Vec3, Quat) {
         let mut pos = Vec3::ZERO;
         let mut rot = Quat::IDENTITY;
         let mut delta = delta;
+        let traction = Self::side_traction(transform, terrain);
 
         if let Some((instr, rem_time)) = self.instruction_handler.current() {
             if delta > rem_time {
                 // println!("Less than remaining time.");
-                let (npos, nrot) = self.calculate_next_pos(transform, instr, rem_time);
+                let (npos, nrot) = self.calculate_next_pos(transform, instr, traction, rem_time);
                 pos += npos;
                 rot *= nrot;
                 delta -= rem_time;
@@ -725,7 +1257,7 @@ impl Arenito {
                 Some((instr, rem_time)) => {
                     let time = delta.min(rem_time);
                     // println!("executing for {}s", time);
-                    let (npos, nrot) = self.calculate_next_pos(transform, instr, time);
+                    let (npos, nrot) = self.calculate_next_pos(transform, instr, traction, time);
                     pos += npos;
                     rot *= nrot;
                     self.instruction_handler.remaining_time -= time;
@@ -764,7 +1296,7 @@ impl Arenito {
         let mut l = 1.0;
         let mut r = 1.0;
 
-        let t = if rot_diff == Quat::IDENTITY {
+        let t = if pos_diff != Vec3::ZERO || rot_diff == Quat::IDENTITY {
             pos_diff.length() * self.velocity_k
         } else {
             let (_, y, _) = rot_diff.to_euler(EulerRot::XYZ);
@@ -793,6 +1325,17 @@ impl DistanceCollision for Arenito {
     }
 }
 
+impl HasCollider for Arenito {
+    /// Box around the body, from the wheels' bottom to the backdoor's hinge,
+    /// and from the backdoor to the brush.
+    fn collider(&self) -> Collider {
+        Collider::new(CollisionShape::Obb {
+            half_extents: Vec3::new(0.83, 0.8, WOZ + 0.1),
+        })
+        .with_offset(Transform::from_xyz(0.075, 0.6, 0.0))
+    }
+}
+
 impl DistanceCollider for Arenito {
     fn collides_with_dist(
         &self,
@@ -804,26 +1347,108 @@ impl DistanceCollider for Arenito {
     }
 }
 
-/// Despawns cans when collided with Arenito
+/// Despawns cans that get into the brush, storing them in Arenito's hopper.
+/// Cans are only picked up while the brush spins and there's room left.
+///
+/// Each can the brush's capture zone went over this tick gets one chance of
+/// being picked up (see `BrushIntake::pickup_probability`), if it isn't,
+/// it's pushed aside. The whole path is tested (see `Arenito::brush_sweep`),
+/// so long frames don't let Arenito jump over cans.
 pub fn eat_cans(
     mut commands: Commands,
+    mut stats: ResMut<EpisodeStats>,
+    mut can_manager: ResMut<CanManager>,
+    mut arenito: Query<(&mut Arenito, &Transform)>,
+    mut cans: Query<(&CanData, &CanId, &mut Transform, Has<Deposited>), Without<Arenito>>,
+) {
+    let (mut arenito, arenito_transform) = arenito.single_mut();
+    if !arenito.brush_on {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    let brush_transform = arenito.brush_transform(arenito_transform);
+    let capture_zone = arenito.brush_sweep(arenito_transform);
+    let approach_speed = arenito.vel.length();
+
+    for (can, id, mut can_transform, deposited) in cans.iter_mut() {
+        if deposited || !capture_zone.intersects(&can.collider().world(&(*can_transform).into())) {
+            continue;
+        }
+
+        let p = arenito.brush.pickup_probability(
+            &brush_transform,
+            &can_transform,
+            approach_speed,
+            arenito.brush_speed,
+        ) * can.pickup_factor();
+
+        if rng.gen_bool(p as f64) && arenito.store_can() {
+            let ent = can_manager.record(*id).entity.unwrap();
+            commands.entity(ent).despawn_recursive();
+            can_manager.store(*id);
+            stats.collected_cans += 1;
+        } else {
+            arenito
+                .brush
+                .push_aside(&brush_transform, &mut can_transform, can.get_radius());
+        }
+    }
+}
+
+/// Same as `eat_cans`, but for debris: it's picked up just like cans,
+/// and counted as a distractor pickup. Debris doesn't take room in
+/// the hopper, since it never gets dumped into the deposit.
+pub fn eat_debris(
+    mut commands: Commands,
+    mut stats: ResMut<EpisodeStats>,
     arenito: Query<(&Arenito, &Transform)>,
-    cans: Query<(&CanData, Entity, &Transform)>,
+    mut debris: Query<(&Debris, Entity, &mut Transform), Without<Arenito>>,
 ) {
     let (arenito, arenito_transform) = arenito.single();
+    if !arenito.brush_on {
+        return;
+    }
 
-    for (can, ent, can_transform) in cans.iter() {
-        if arenito.collides_with_dist(can, arenito_transform, can_transform) {
+    let mut rng = rand::thread_rng();
+    let brush_transform = arenito.brush_transform(arenito_transform);
+    let capture_zone = arenito.brush_sweep(arenito_transform);
+
+    for (debris, ent, mut debris_transform) in debris.iter_mut() {
+        if !debris.kind.pickable()
+            || !capture_zone.intersects(&debris.collider().world(&(*debris_transform).into()))
+        {
+            continue;
+        }
+
+        if rng.gen_bool(debris.kind.pickup_probability() as f64) {
             commands.entity(ent).despawn();
+            stats.distractor_pickups += 1;
+        } else {
+            arenito
+                .brush
+                .push_aside(&brush_transform, &mut debris_transform, debris.get_radius());
         }
     }
 }
 
-/// Spawns ´cans´ wherever Arenito is positioned.
+fn update_hopper_text(
+    arenito: Query<&Arenito, Changed<Arenito>>,
+    mut text: Query<&mut Text, With<ControlText>>,
+) {
+    if let Ok(arenito) = arenito.get_single() {
+        let mut text = text.single_mut();
+        text.sections[3].value = format!("{}/{}", arenito.hopper, arenito.hopper_capacity);
+    }
+}
+
+/// Spawns ´cans´ (the first ones picked up) wherever Arenito is positioned.
+/// Dumped cans are scored (deposited or littered) by `score_dumped_cans`.
 pub fn dump_cans(
     commands: &mut Commands,
     can_manager: &mut ResMut<CanManager>,
     arenito_transform: &Transform,
+    terrain: &Terrain,
     cans: u8,
 ) {
     const MAX_DUMPED_CANS: u8 = 8;
@@ -833,8 +1458,9 @@ pub fn dump_cans(
     let mut rng = rand::thread_rng();
     let mut can_positions = Vec::<Vec3>::new();
     let step = DISPERSION_LENGTH / MAX_DUMPED_CANS as f32;
+    let ids = can_manager.take_from_hopper(cans);
 
-    for i in 0..cans {
+    for i in 0..ids.len() {
         let lp = step * (i + 1) as f32;
         let range = (lp * DISPERSION_WIDTH) / (2.0 * DISPERSION_LENGTH);
         let can_pos = Vec3::new(-lp - 1.0, 0.0, rng.gen_range(-range..range));
@@ -843,16 +1469,107 @@ pub fn dump_cans(
             .push(arenito_transform.rotation.mul_vec3(can_pos) + arenito_transform.translation);
     }
 
-    for d in can_positions.iter() {
-        can_manager.spawn(
-            commands,
-            CanData::default(),
-            Transform::from_translation(*d).with_rotation(Quat::from_euler(
-                EulerRot::XYZ,
-                0.0,
-                rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI),
-                1.56,
-            )),
-        );
+    for (id, d) in ids.iter().zip(can_positions.iter()) {
+        let can_data = can_manager.record(*id).data;
+        let ry = rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI);
+        let ground = terrain.height_at(d.x, d.z);
+        let can = can_manager.put_back(commands, *id, can_data.placement(d.x, d.z, ry, ground));
+        commands.entity(can).insert(DumpedCan);
+    }
+}
+
+#[cfg(test)]
+mod hopper_tests {
+    use super::*;
+    use crate::{
+        cans::{CanCondition, CanPose},
+        debris::DebrisKind,
+        terrain::TerrainSource,
+    };
+
+    // Arenito on flat terrain, and where its brush is
+    fn on_flat_terrain() -> (Arenito, Transform, Terrain) {
+        let terrain = Terrain::new(TerrainSource::Flat, 10.0, 6.0);
+        let config = ArenitoConfig::default();
+        let mut transform = config.initial_pos;
+        Arenito::follow_terrain(&mut transform, &terrain);
+        let mut arenito = Arenito::new(&config);
+        arenito.last_transform = transform;
+
+        (arenito, transform, terrain)
+    }
+
+    #[test]
+    fn hopper_doesnt_store_over_capacity() {
+        let mut arenito = Arenito::new(&ArenitoConfig {
+            hopper_capacity: 2,
+            ..default()
+        });
+
+        assert!(arenito.store_can());
+        assert!(arenito.store_can());
+        assert!(!arenito.store_can());
+    }
+
+    #[test]
+    fn hopper_releases_at_most_stored_cans() {
+        let mut arenito = Arenito::new(&ArenitoConfig::default());
+        arenito.store_can();
+        arenito.store_can();
+
+        assert_eq!(arenito.release_cans(5), 2);
+        assert_eq!(arenito.release_cans(1), 0);
+    }
+
+    #[test]
+    fn brush_reaches_low_cans() {
+        let (arenito, transform, terrain) = on_flat_terrain();
+        let brush = arenito.brush_transform(&transform).translation;
+        let ground = terrain.height_at(brush.x, brush.z);
+        let half_buried = CanData {
+            pose: CanPose::HalfBuried,
+            ..default()
+        };
+        let crushed = CanData {
+            condition: CanCondition::Crushed,
+            pose: CanPose::Standing,
+            ..default()
+        };
+
+        for can in [half_buried, crushed] {
+            let placement = can.placement(brush.x, brush.z, 0.3, ground);
+            assert!(
+                arenito
+                    .brush_sweep(&transform)
+                    .intersects(&can.collider().world(&placement.into())),
+                "brush misses {:?}",
+                can
+            );
+        }
+    }
+
+    #[test]
+    fn brush_reaches_pickable_debris() {
+        let (arenito, transform, terrain) = on_flat_terrain();
+        let brush = arenito.brush_transform(&transform).translation;
+        let ground = terrain.height_at(brush.x, brush.z);
+
+        for kind in [
+            DebrisKind::Bottle,
+            DebrisKind::BottleCap,
+            DebrisKind::Shell,
+            DebrisKind::Seaweed,
+            DebrisKind::DarkRock,
+        ] {
+            let debris = Debris { kind };
+            let placement = debris.placement(brush.x, brush.z, 0.3, ground);
+            assert!(
+                arenito
+                    .brush_sweep(&transform)
+                    .intersects(&debris.collider().world(&placement.into())),
+                "brush misses {:?}",
+                kind
+            );
+        }
     }
 }
@@ -0,0 +1,194 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DoorState {
+    Closed,
+    Opening,
+    Open,
+    Closing,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExtensionState {
+    Retracted,
+    Extending,
+    Retracting,
+}
+
+/// Arenito's backdoor, the way out of the hopper.
+///
+/// Mimics the firmware: the door motor runs until a limit switch
+/// (`ls_up` when open, `ls_down` when closed) is pressed, and the
+/// extension (`ExtendBackdoor`) goes out and back in on a timer.
+#[derive(Clone)]
+pub struct Backdoor {
+    // 0 is closed, 1 is open
+    openness: f32,
+    state: DoorState,
+    // 0 is retracted, 1 is extended
+    extension: f32,
+    extension_state: ExtensionState,
+}
+
+impl Backdoor {
+    // how long it takes the motors to move, in seconds
+    // these must be lower than the firmware's BACKDOOR_TIMEOUT
+    const OPEN_TIME: f32 = 0.8;
+    const CLOSE_TIME: f32 = 0.9;
+    // BACKDOOR_EXT_TIME_UP, BACKDOOR_EXT_TIME_DOWN
+    const EXTEND_TIME: f32 = 1.5;
+    const RETRACT_TIME: f32 = 1.0;
+
+    pub const OPEN_ANGLE: f32 = 1.4; // ~80°
+    pub const EXTENSION_LENGTH: f32 = 0.3;
+
+    pub fn new() -> Self {
+        Backdoor {
+            openness: 0.0,
+            state: DoorState::Closed,
+            extension: 0.0,
+            extension_state: ExtensionState::Retracted,
+        }
+    }
+
+    pub fn open(&mut self) {
+        if self.state != DoorState::Open {
+            self.state = DoorState::Opening;
+        }
+    }
+
+    pub fn close(&mut self) {
+        if self.state != DoorState::Closed {
+            self.state = DoorState::Closing;
+        }
+    }
+
+    /// Starts the extension's out-and-in cycle.
+    pub fn extend(&mut self) {
+        self.extension_state = ExtensionState::Extending;
+    }
+
+    /// Moves the door and its extension, given some time in seconds.
+    pub fn update(&mut self, delta: f32) {
+        match self.state {
+            DoorState::Opening => {
+                self.openness += delta / Self::OPEN_TIME;
+                if self.openness >= 1.0 {
+                    self.openness = 1.0;
+                    self.state = DoorState::Open;
+                }
+            }
+            DoorState::Closing => {
+                self.openness -= delta / Self::CLOSE_TIME;
+                if self.openness <= 0.0 {
+                    self.openness = 0.0;
+                    self.state = DoorState::Closed;
+                }
+            }
+            _ => {}
+        }
+
+        match self.extension_state {
+            ExtensionState::Extending => {
+                self.extension += delta / Self::EXTEND_TIME;
+                if self.extension >= 1.0 {
+                    self.extension = 1.0;
+                    self.extension_state = ExtensionState::Retracting;
+                }
+            }
+            ExtensionState::Retracting => {
+                self.extension -= delta / Self::RETRACT_TIME;
+                if self.extension <= 0.0 {
+                    self.extension = 0.0;
+                    self.extension_state = ExtensionState::Retracted;
+                }
+            }
+            ExtensionState::Retracted => {}
+        }
+    }
+
+    pub fn state(&self) -> DoorState {
+        self.state
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state == DoorState::Open
+    }
+
+    /// Whether the extension is done moving.
+    pub fn is_retracted(&self) -> bool {
+        self.extension_state == ExtensionState::Retracted
+    }
+
+    /// Returns the limit switches' state: (`ls_up`, `ls_down`).
+    /// A switch reads true when pressed.
+    pub fn limit_switches(&self) -> (bool, bool) {
+        (self.openness >= 1.0, self.openness <= 0.0)
+    }
+
+    /// Door's current rotation, in radians.
+    pub fn angle(&self) -> f32 {
+        self.openness * Self::OPEN_ANGLE
+    }
+
+    /// How far the extension is out.
+    pub fn extension_offset(&self) -> f32 {
+        self.extension * Self::EXTENSION_LENGTH
+    }
+}
+
+impl Default for Backdoor {
+    fn default() -> Self {
+        Backdoor::new()
+    }
+}
+
+#[cfg(test)]
+mod backdoor_tests {
+    use super::*;
+
+    #[test]
+    fn test_closed_door_presses_lower_switch() {
+        let backdoor = Backdoor::new();
+
+        assert_eq!(backdoor.limit_switches(), (false, true));
+    }
+
+    #[test]
+    fn test_door_takes_time_to_open() {
+        let mut backdoor = Backdoor::new();
+        backdoor.open();
+        backdoor.update(0.4);
+
+        assert_eq!(backdoor.state(), DoorState::Opening);
+        assert_eq!(backdoor.limit_switches(), (false, false));
+
+        backdoor.update(0.5);
+        assert!(backdoor.is_open());
+        assert_eq!(backdoor.limit_switches(), (true, false));
+    }
+
+    #[test]
+    fn test_door_closes() {
+        let mut backdoor = Backdoor::new();
+        backdoor.open();
+        backdoor.update(1.0);
+        backdoor.close();
+        backdoor.update(1.0);
+
+        assert_eq!(backdoor.state(), DoorState::Closed);
+        assert_eq!(backdoor.angle(), 0.0);
+    }
+
+    #[test]
+    fn test_extension_goes_out_and_back_in() {
+        let mut backdoor = Backdoor::new();
+        backdoor.extend();
+        backdoor.update(1.5);
+
+        assert!(!backdoor.is_retracted());
+        assert_eq!(backdoor.extension_offset(), Backdoor::EXTENSION_LENGTH);
+
+        backdoor.update(1.0);
+        assert!(backdoor.is_retracted());
+        assert_eq!(backdoor.extension_offset(), 0.0);
+    }
+}
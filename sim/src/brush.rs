@@ -0,0 +1,208 @@
+use crate::collision_shape::{Collider, CollisionShape};
+use bevy::prelude::*;
+
+/// Arenito's brush intake model.
+///
+/// The brush is a roller that spins around its z axis (`width` long).
+/// Cans can be picked up when they're in the capture zone: a box around
+/// the roller, `reach` units in front and behind it, and `height` units
+/// above and below it.
+///
+/// Being in the capture zone doesn't guarantee a pickup: the roller has
+/// a harder time with cans that are standing or perpendicular to it,
+/// and with cans approached faster than the brush can pull them in.
+#[derive(Clone, Copy)]
+pub struct BrushIntake {
+    pub width: f32,
+    pub reach: f32,
+    pub height: f32,
+    pub radius: f32,
+}
+
+impl BrushIntake {
+    // pickup probability factors
+    const STANDING_FACTOR: f32 = 0.4;
+    const PERPENDICULAR_FACTOR: f32 = 0.6;
+
+    /// Returns `point` relative to the brush.
+    fn to_local(brush_transform: &Transform, point: Vec3) -> Vec3 {
+        brush_transform
+            .compute_matrix()
+            .inverse()
+            .transform_point3(point)
+    }
+
+    /// Checks whether `point` is inside the capture zone.
+    pub fn in_capture_zone(&self, brush_transform: &Transform, point: Vec3) -> bool {
+        let p = Self::to_local(brush_transform, point);
+        p.x.abs() <= self.reach && p.y.abs() <= self.height && p.z.abs() <= self.width / 2.0
+    }
+
+    /// The capture zone, as a collider centered on the brush.
+    pub fn capture_zone(&self) -> Collider {
+        Collider::new(CollisionShape::Obb {
+            half_extents: Vec3::new(self.reach, self.height, self.width / 2.0),
+        })
+    }
+
+    /// Probability of picking up a can, given its orientation, how fast
+    /// it's approached (`approach_speed`, in units per second) and how fast
+    /// the brush spins (`brush_speed`, in radians per second).
+    pub fn pickup_probability(
+        &self,
+        brush_transform: &Transform,
+        can_transform: &Transform,
+        approach_speed: f32,
+        brush_speed: f32,
+    ) -> f32 {
+        if brush_speed <= 0.0 {
+            return 0.0;
+        }
+
+        let can_axis = can_transform.rotation.mul_vec3(Vec3::Y);
+        let brush_axis = brush_transform.rotation.mul_vec3(Vec3::Z);
+
+        let standing = can_axis.y.abs();
+        let aligned = can_axis.dot(brush_axis).abs();
+        let orientation = standing * Self::STANDING_FACTOR
+            + (1.0 - standing)
+                * (Self::PERPENDICULAR_FACTOR + (1.0 - Self::PERPENDICULAR_FACTOR) * aligned);
+
+        // the brush's surface has to be at least as fast as the approach
+        let surface_speed = brush_speed * self.radius;
+        let speed = if approach_speed <= surface_speed {
+            1.0
+        } else {
+            surface_speed / approach_speed
+        };
+
+        (orientation * speed).clamp(0.0, 1.0)
+    }
+
+    /// Moves a can that wasn't picked up out of the capture zone,
+    /// to the side of the brush it's closer to.
+    pub fn push_aside(
+        &self,
+        brush_transform: &Transform,
+        can_transform: &mut Transform,
+        can_radius: f32,
+    ) {
+        let p = Self::to_local(brush_transform, can_transform.translation);
+        let side = if p.z < 0.0 { -1.0 } else { 1.0 };
+        let pushed = Vec3::new(p.x, p.y, side * (self.width / 2.0 + can_radius));
+        let pushed = brush_transform.transform_point(pushed);
+
+        can_transform.translation.x = pushed.x;
+        can_transform.translation.z = pushed.z;
+    }
+}
+
+impl Default for BrushIntake {
+    fn default() -> Self {
+        BrushIntake {
+            width: 0.9,
+            reach: 0.3,
+            height: 0.5,
+            radius: 0.15,
+        }
+    }
+}
+
+#[cfg(test)]
+mod brush_intake_tests {
+    use super::*;
+    use crate::collision_shape::WorldShape;
+
+    fn lying_can(yaw: f32) -> Transform {
+        Transform::from_xyz(0.1, 0.0, 0.0).with_rotation(Quat::from_euler(
+            EulerRot::XYZ,
+            0.0,
+            yaw,
+            1.56,
+        ))
+    }
+
+    #[test]
+    fn test_capture_zone() {
+        let brush = BrushIntake::default();
+        let t = Transform::from_xyz(1.0, 0.4, 0.0);
+
+        assert!(brush.in_capture_zone(&t, Vec3::new(1.1, 0.1, 0.4)));
+        assert!(!brush.in_capture_zone(&t, Vec3::new(1.1, 0.1, 0.6)));
+        assert!(!brush.in_capture_zone(&t, Vec3::new(1.5, 0.1, 0.0)));
+    }
+
+    #[test]
+    fn test_capture_zone_follows_rotation() {
+        let brush = BrushIntake::default();
+        let t = Transform::from_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2));
+
+        // the roller now lies along the x axis
+        assert!(brush.in_capture_zone(&t, Vec3::new(0.4, 0.0, 0.0)));
+        assert!(!brush.in_capture_zone(&t, Vec3::new(0.0, 0.0, 0.4)));
+    }
+
+    #[test]
+    fn test_capture_zone_sweeps_over_skipped_cans() {
+        let brush = BrushIntake::default();
+        let from = Transform::IDENTITY;
+        let to = Transform::from_xyz(3.0, 0.0, 0.0);
+        let can = Vec3::new(1.5, 0.0, 0.2);
+
+        // a long frame: the can is out of the zone at both ends
+        assert!(!brush.in_capture_zone(&from, can));
+        assert!(!brush.in_capture_zone(&to, can));
+        assert!(brush
+            .capture_zone()
+            .sweep(&from.into(), &to.into())
+            .intersects(&WorldShape::point(can)));
+    }
+
+    #[test]
+    fn test_no_pickup_with_brush_off() {
+        let brush = BrushIntake::default();
+
+        assert_eq!(
+            brush.pickup_probability(&Transform::IDENTITY, &lying_can(0.0), 1.0, 0.0),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_aligned_cans_are_easier_than_perpendicular_and_standing() {
+        let brush = BrushIntake::default();
+        let t = Transform::IDENTITY;
+
+        // lying can with its axis on x: perpendicular to the roller
+        let perpendicular = brush.pickup_probability(&t, &lying_can(0.0), 1.0, 10.0);
+        // axis on z: parallel to the roller
+        let aligned =
+            brush.pickup_probability(&t, &lying_can(std::f32::consts::FRAC_PI_2), 1.0, 10.0);
+        let standing = brush.pickup_probability(&t, &Transform::IDENTITY, 1.0, 10.0);
+
+        assert!(aligned > perpendicular);
+        assert!(perpendicular > standing);
+    }
+
+    #[test]
+    fn test_fast_approach_lowers_pickup_probability() {
+        let brush = BrushIntake::default();
+        let can = lying_can(0.0);
+
+        let slow = brush.pickup_probability(&Transform::IDENTITY, &can, 1.0, 10.0);
+        let fast = brush.pickup_probability(&Transform::IDENTITY, &can, 4.0, 10.0);
+
+        assert!(fast < slow);
+    }
+
+    #[test]
+    fn test_push_aside_leaves_capture_zone() {
+        let brush = BrushIntake::default();
+        let t = Transform::from_xyz(1.0, 0.4, 0.0);
+        let mut can = Transform::from_xyz(1.1, 0.1, 0.2);
+
+        brush.push_aside(&t, &mut can, 0.15);
+        assert!(!brush.in_capture_zone(&t, can.translation));
+        assert!(can.translation.z > 0.0);
+    }
+}
@@ -0,0 +1,380 @@
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        main_graph::node::CAMERA_DRIVER,
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+        texture::TextureFormatPixelInfo,
+        view::RenderLayers,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    window::{WindowRef, WindowResolution},
+};
+use image::{DynamicImage, RgbImage, RgbaImage};
+use std::sync::{Arc, Mutex};
+
+// wgpu's row alignment for texture to buffer copies
+const COPY_BYTES_PER_ROW_ALIGNMENT: u32 = 256;
+
+/// Renders onboard cameras to textures, and reads their frames back
+/// from the GPU when asked to (see `CameraReadback`).
+///
+/// No windows are involved, unless a camera wants a preview.
+pub struct CameraTargetPlugin;
+
+impl Plugin for CameraTargetPlugin {
+    fn build(&self, app: &mut App) {
+        let readback = CameraReadback::default();
+        app.insert_resource(readback.clone())
+            .add_systems(Update, spawn_previews);
+
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .insert_resource(readback)
+            .init_resource::<FrameCopies>()
+            .add_systems(ExtractSchedule, extract_frame_requests)
+            .add_systems(Render, map_frames.in_set(RenderSet::Cleanup));
+
+        let mut graph = render_app.world.resource_mut::<RenderGraph>();
+        graph.add_node(FrameCopyNode::NAME, FrameCopyNode);
+        graph.add_node_edge(CAMERA_DRIVER, FrameCopyNode::NAME);
+    }
+}
+
+/// An onboard camera's texture, the camera is addressed by `id`.
+#[derive(Component, Clone)]
+pub struct CameraTarget {
+    pub id: usize,
+    pub image: Handle<Image>,
+    /// Show what the camera sees in a window.
+    pub preview: bool,
+}
+
+impl CameraTarget {
+    // what cameras render to
+    const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+    // first render layer used by previews
+    const PREVIEW_LAYER: u8 = 16;
+
+    /// Returns an empty texture cameras can render to (and be copied from).
+    pub fn image(size: UVec2) -> Image {
+        let size = Extent3d {
+            width: size.x,
+            height: size.y,
+            ..default()
+        };
+        let mut image = Image {
+            texture_descriptor: TextureDescriptor {
+                label: None,
+                size,
+                dimension: TextureDimension::D2,
+                format: Self::FORMAT,
+                mip_level_count: 1,
+                sample_count: 1,
+                usage: TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_DST
+                    | TextureUsages::COPY_SRC
+                    | TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            },
+            ..default()
+        };
+        image.resize(size);
+        image
+    }
+
+    /// Render layer of the camera's preview, None if there are no layers left.
+    fn preview_layer(&self) -> Option<RenderLayers> {
+        (Self::PREVIEW_LAYER as usize)
+            .checked_add(self.id)
+            .filter(|layer| *layer < RenderLayers::TOTAL_LAYERS)
+            .map(|layer| RenderLayers::layer(layer as u8))
+    }
+
+    pub fn render_target(&self) -> RenderTarget {
+        RenderTarget::Image(self.image.clone())
+    }
+}
+
+/// A camera's frame, read back from the GPU.
+pub struct CameraFrame {
+    pub id: usize,
+    pub image: RgbImage,
+}
+
+/// Requests frames from cameras (by id), and receives them once
+/// they're read back, a frame or two later.
+///
+/// Shared by the main and the render world.
+#[derive(Resource, Clone, Default)]
+pub struct CameraReadback {
+    requests: Arc<Mutex<Vec<usize>>>,
+    frames: Arc<Mutex<Vec<CameraFrame>>>,
+}
+
+impl CameraReadback {
+    pub fn request(&self, id: usize) {
+        self.requests.lock().unwrap().push(id);
+    }
+
+    /// Returns the frames read back since the last call.
+    pub fn receive(&self) -> Vec<CameraFrame> {
+        std::mem::take(&mut *self.frames.lock().unwrap())
+    }
+}
+
+/// A frame on its way back from the GPU.
+struct FrameCopy {
+    id: usize,
+    image: Handle<Image>,
+    size: UVec2,
+    buffer: Buffer,
+    // Some once the buffer's mapped (or failed to)
+    mapped: Option<Arc<Mutex<Option<bool>>>>,
+}
+
+#[derive(Resource, Default)]
+struct FrameCopies(Vec<FrameCopy>);
+
+/// Bytes per row in a copied texture: rows are padded.
+fn padded_row(width: u32) -> u32 {
+    let row = width * CameraTarget::FORMAT.pixel_size() as u32;
+    row.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+/// Strips the copy's row padding, returns the frame.
+fn unpad(data: &[u8], size: UVec2) -> Option<RgbImage> {
+    let row = (size.x * CameraTarget::FORMAT.pixel_size() as u32) as usize;
+    let rgba = data
+        .chunks(padded_row(size.x) as usize)
+        .take(size.y as usize)
+        .flat_map(|padded| &padded[..row])
+        .copied()
+        .collect();
+
+    RgbaImage::from_raw(size.x, size.y, rgba).map(|img| DynamicImage::ImageRgba8(img).to_rgb8())
+}
+
+/// Takes the frames requested this frame, and gets their buffers ready.
+fn extract_frame_requests(
+    readback: Res<CameraReadback>,
+    targets: Extract<Query<&CameraTarget>>,
+    render_device: Res<RenderDevice>,
+    images: Extract<Res<Assets<Image>>>,
+    mut copies: ResMut<FrameCopies>,
+) {
+    let requests = std::mem::take(&mut *readback.requests.lock().unwrap());
+
+    for id in requests {
+        let Some(target) = targets.iter().find(|target| target.id == id) else {
+            println!("No camera renders to target {}.", id);
+            continue;
+        };
+        let Some(image) = images.get(&target.image) else {
+            continue;
+        };
+
+        let size = image.size();
+        copies.0.push(FrameCopy {
+            id,
+            image: target.image.clone(),
+            size,
+            buffer: render_device.create_buffer(&BufferDescriptor {
+                label: Some("camera-frame-buffer"),
+                size: (padded_row(size.x) * size.y) as u64,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            mapped: None,
+        });
+    }
+}
+
+/// Copies requested frames to their buffers, once cameras are done rendering.
+struct FrameCopyNode;
+
+impl FrameCopyNode {
+    const NAME: &'static str = "camera_frame_copy";
+}
+
+impl Node for FrameCopyNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let gpu_images = world.resource::<RenderAssets<Image>>();
+
+        for copy in world.resource::<FrameCopies>().0.iter() {
+            if copy.mapped.is_some() {
+                continue;
+            }
+            let Some(gpu_image) = gpu_images.get(&copy.image) else {
+                continue;
+            };
+
+            render_context.command_encoder().copy_texture_to_buffer(
+                gpu_image.texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer: &copy.buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_row(copy.size.x)),
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: copy.size.x,
+                    height: copy.size.y,
+                    ..default()
+                },
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Maps this frame's copies, and sends back the ones that are mapped
+/// (buffers get mapped when the GPU's polled, at the next submit).
+fn map_frames(readback: Res<CameraReadback>, mut copies: ResMut<FrameCopies>) {
+    let mut frames = Vec::new();
+
+    copies.0.retain_mut(|copy| {
+        let Some(mapped) = &copy.mapped else {
+            let mapped = Arc::new(Mutex::new(None));
+            let on_map = mapped.clone();
+            copy.buffer
+                .slice(..)
+                .map_async(MapMode::Read, move |result| {
+                    *on_map.lock().unwrap() = Some(result.is_ok());
+                });
+            copy.mapped = Some(mapped);
+            return true;
+        };
+
+        let done = *mapped.lock().unwrap();
+        match done {
+            Some(true) => {
+                let image = unpad(&copy.buffer.slice(..).get_mapped_range(), copy.size);
+                copy.buffer.unmap();
+                match image {
+                    Some(image) => frames.push(CameraFrame { id: copy.id, image }),
+                    None => println!("Cannot read camera {}'s frame!", copy.id),
+                }
+                false
+            }
+            Some(false) => {
+                println!("Cannot read camera {}'s frame!", copy.id);
+                false
+            }
+            None => true,
+        }
+    });
+
+    readback.frames.lock().unwrap().extend(frames);
+}
+
+/// Opens a window for every camera that wants a preview, showing its image.
+fn spawn_previews(
+    mut commands: Commands,
+    images: Res<Assets<Image>>,
+    targets: Query<(&CameraTarget, Option<&Name>), Added<CameraTarget>>,
+) {
+    for (target, name) in targets.iter().filter(|(target, _)| target.preview) {
+        let size = images
+            .get(&target.image)
+            .map(|image| image.size_f32())
+            .unwrap_or(Vec2::splat(512.0));
+        let Some(layer) = target.preview_layer() else {
+            println!(
+                "Out of preview layers, camera {} can't be previewed.",
+                target.id
+            );
+            continue;
+        };
+
+        let window = commands
+            .spawn(Window {
+                title: name.map_or(format!("Camera {}", target.id), |name| name.to_string()),
+                resolution: WindowResolution::new(size.x, size.y),
+                resizable: false,
+                ..default()
+            })
+            .id();
+        commands.spawn((
+            Camera2dBundle {
+                camera: Camera {
+                    target: RenderTarget::Window(WindowRef::Entity(window)),
+                    // after the camera it shows
+                    order: 1,
+                    ..default()
+                },
+                ..default()
+            },
+            layer,
+        ));
+        commands.spawn((
+            SpriteBundle {
+                texture: target.image.clone(),
+                ..default()
+            },
+            layer,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod camera_target_tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_are_padded_to_alignment() {
+        assert_eq!(padded_row(64), 256);
+        assert_eq!(padded_row(65), 512);
+        assert_eq!(padded_row(512), 2048);
+    }
+
+    #[test]
+    fn test_unpad_drops_padding_and_alpha() {
+        let size = UVec2::new(2, 2);
+        let row = padded_row(2) as usize;
+        let mut data = vec![0; row * 2];
+        data[..8].copy_from_slice(&[1, 2, 3, 255, 4, 5, 6, 255]);
+        data[row..row + 8].copy_from_slice(&[7, 8, 9, 255, 10, 11, 12, 255]);
+
+        let img = unpad(&data, size).unwrap();
+        assert_eq!(img.into_raw(), (1..=12).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_preview_layers_run_out() {
+        let target = |id| CameraTarget {
+            id,
+            image: Handle::default(),
+            preview: true,
+        };
+
+        assert_eq!(target(0).preview_layer(), Some(RenderLayers::layer(16)));
+        assert_eq!(target(15).preview_layer(), Some(RenderLayers::layer(31)));
+        assert_eq!(target(16).preview_layer(), None);
+        assert_eq!(target(usize::MAX - 4).preview_layer(), None);
+    }
+
+    #[test]
+    fn test_target_images_can_be_copied_from() {
+        let image = CameraTarget::image(UVec2::new(640, 480));
+
+        assert_eq!(image.size(), UVec2::new(640, 480));
+        assert!(image
+            .texture_descriptor
+            .usage
+            .contains(TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT));
+    }
+}
@@ -1,20 +1,128 @@
-use crate::collision::DistanceCollision;
-use bevy::prelude::*;
+use crate::collision::{CollisionLayers, DistanceCollision, Obstacle, ObstacleProxy};
+use crate::collision_shape::{Collider, CollisionShape, HasCollider};
+use bevy::{prelude::*, render::mesh::VertexAttributeValues, utils::HashMap};
+use rand::{distributions::WeightedIndex, prelude::*, rngs::StdRng};
+use std::{collections::VecDeque, time::Duration};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum CanSize {
     Big,
     Small,
 }
 
+impl CanSize {
+    const ALL: [CanSize; 2] = [CanSize::Big, CanSize::Small];
+
+    /// Intact can's (radius, height).
+    fn dimensions(&self) -> (f32, f32) {
+        match self {
+            CanSize::Big => (0.15, 0.47),
+            CanSize::Small => (0.13, 0.37),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum CanTexture {
     Shiny,
     Dirty,
 }
 
-#[derive(Component)]
+/// Brand label, a colored band around the can.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CanLabel {
+    Unlabeled,
+    Red,
+    Blue,
+    Green,
+    Silver,
+}
+
+impl CanLabel {
+    fn color(&self) -> Option<Color> {
+        match self {
+            CanLabel::Unlabeled => None,
+            CanLabel::Red => Some(Color::rgb(0.8, 0.05, 0.05)),
+            CanLabel::Blue => Some(Color::rgb(0.05, 0.15, 0.7)),
+            CanLabel::Green => Some(Color::rgb(0.1, 0.6, 0.15)),
+            CanLabel::Silver => Some(Color::rgb(0.75, 0.75, 0.78)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CanCondition {
+    Intact,
+    Dented,
+    Crushed,
+}
+
+impl CanCondition {
+    const ALL: [CanCondition; 3] = [
+        CanCondition::Intact,
+        CanCondition::Dented,
+        CanCondition::Crushed,
+    ];
+
+    /// How much the can's (radius, height) change.
+    fn deformation(&self) -> (f32, f32) {
+        match self {
+            CanCondition::Intact => (1.0, 1.0),
+            CanCondition::Dented => (1.0, 0.95),
+            CanCondition::Crushed => (1.2, 0.35),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CanPose {
+    Lying,
+    Standing,
+    HalfBuried,
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
 pub struct CanData {
     pub size: CanSize,
     pub texture: CanTexture,
+    pub label: CanLabel,
+    pub condition: CanCondition,
+    pub pose: CanPose,
+}
+
+impl CanData {
+    // half buried cans stick out of the sand at this angle
+    const HALF_BURIED_TILT: f32 = 0.5;
+    // half buried cans are a lot harder to pull out of the sand
+    const HALF_BURIED_PICKUP_FACTOR: f32 = 0.25;
+
+    /// Can's (radius, height), considering its size and condition.
+    pub fn dimensions(&self) -> (f32, f32) {
+        let (r, h) = self.size.dimensions();
+        let (kr, kh) = self.condition.deformation();
+        (r * kr, h * kh)
+    }
+
+    /// Returns the can's transform when placed at (x, z), rotated `ry`
+    /// radians around the y axis, given the ground's height there.
+    pub fn placement(&self, x: f32, z: f32, ry: f32, ground: f32) -> Transform {
+        let (r, h) = self.dimensions();
+        let (y, rz) = match self.pose {
+            CanPose::Lying => (ground + r, std::f32::consts::FRAC_PI_2),
+            CanPose::Standing => (ground + h / 2.0, 0.0),
+            CanPose::HalfBuried => (ground, Self::HALF_BURIED_TILT),
+        };
+
+        Transform::from_xyz(x, y, z).with_rotation(Quat::from_euler(EulerRot::XYZ, 0.0, ry, rz))
+    }
+
+    /// Scales the brush's pickup probability, given how the can's placed.
+    pub fn pickup_factor(&self) -> f32 {
+        match self.pose {
+            CanPose::HalfBuried => Self::HALF_BURIED_PICKUP_FACTOR,
+            _ => 1.0,
+        }
+    }
 }
 
 impl Default for CanData {
@@ -22,89 +130,349 @@ impl Default for CanData {
         CanData {
             size: CanSize::Big,
             texture: CanTexture::Shiny,
+            label: CanLabel::Unlabeled,
+            condition: CanCondition::Intact,
+            pose: CanPose::Lying,
         }
     }
 }
 
 impl DistanceCollision for CanData {
+    /// Radius of the sphere that contains the whole can.
     fn get_radius(&self) -> f32 {
-        0.3
+        let (r, h) = self.dimensions();
+        (r * r + h * h / 4.0).sqrt()
+    }
+}
+
+impl HasCollider for CanData {
+    /// Cylinder as big as the can (dents and all).
+    fn collider(&self) -> Collider {
+        let (radius, height) = self.dimensions();
+        Collider::new(CollisionShape::Cylinder {
+            radius,
+            half_height: height / 2.0,
+        })
+    }
+}
+
+/// Weighted choices for every can feature.
+/// Scenes use it to spawn a mix of cans (see `SceneData::can_variety`).
+#[derive(Clone)]
+pub struct CanVariety {
+    pub seed: u64,
+    pub sizes: Vec<(CanSize, f32)>,
+    pub textures: Vec<(CanTexture, f32)>,
+    pub labels: Vec<(CanLabel, f32)>,
+    pub conditions: Vec<(CanCondition, f32)>,
+    pub poses: Vec<(CanPose, f32)>,
+}
+
+impl CanVariety {
+    /// A mix of cans resembling the ones found on a beach.
+    pub fn beach_litter(seed: u64) -> Self {
+        CanVariety {
+            seed,
+            sizes: vec![(CanSize::Big, 0.6), (CanSize::Small, 0.4)],
+            textures: vec![(CanTexture::Shiny, 0.5), (CanTexture::Dirty, 0.5)],
+            labels: vec![
+                (CanLabel::Unlabeled, 0.2),
+                (CanLabel::Red, 0.3),
+                (CanLabel::Blue, 0.2),
+                (CanLabel::Green, 0.15),
+                (CanLabel::Silver, 0.15),
+            ],
+            conditions: vec![
+                (CanCondition::Intact, 0.5),
+                (CanCondition::Dented, 0.3),
+                (CanCondition::Crushed, 0.2),
+            ],
+            poses: vec![
+                (CanPose::Lying, 0.6),
+                (CanPose::Standing, 0.2),
+                (CanPose::HalfBuried, 0.2),
+            ],
+        }
+    }
+
+    /// Returns a seeded random generator, to draw cans from.
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed)
+    }
+
+    /// Draws a can.
+    pub fn sample(&self, rng: &mut impl Rng) -> CanData {
+        CanData {
+            size: Self::pick(&self.sizes, rng),
+            texture: Self::pick(&self.textures, rng),
+            label: Self::pick(&self.labels, rng),
+            condition: Self::pick(&self.conditions, rng),
+            pose: Self::pick(&self.poses, rng),
+        }
+    }
+
+    fn pick<T: Copy>(choices: &[(T, f32)], rng: &mut impl Rng) -> T {
+        let weights = WeightedIndex::new(choices.iter().map(|c| c.1)).unwrap();
+        choices[weights.sample(rng)].0
+    }
+}
+
+impl Default for CanVariety {
+    /// Every can is the default one.
+    fn default() -> Self {
+        let can = CanData::default();
+        CanVariety {
+            seed: 0,
+            sizes: vec![(can.size, 1.0)],
+            textures: vec![(can.texture, 1.0)],
+            labels: vec![(can.label, 1.0)],
+            conditions: vec![(can.condition, 1.0)],
+            poses: vec![(can.pose, 1.0)],
+        }
     }
 }
 
+/// Identifies a can through its whole lifecycle, even while it's
+/// in the hopper (and has no entity).
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CanId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CanState {
+    OnSand,
+    InHopper,
+    Deposited,
+    LostInWater,
+}
+
+/// Everything the `CanManager` knows about a can.
+#[derive(Clone, Copy, Debug)]
+pub struct CanRecord {
+    pub data: CanData,
+    pub state: CanState,
+    // None while the can isn't spawned
+    pub entity: Option<Entity>,
+    // scene position the can was spawned at, while it's still there
+    pub slot: Option<usize>,
+}
+
+/// Spawns cans and keeps track of them.
+///
+/// Every can gets a `CanId` and a `CanRecord`, which is kept until
+/// the simulation ends. Cans in the hopper are despawned, and spawned
+/// again (keeping their id) when dumped.
 #[derive(Resource)]
 pub struct CanManager {
-    dirty_material_handle: Option<Handle<StandardMaterial>>,
-    shiny_material_handle: Option<Handle<StandardMaterial>>,
-    big_mesh_handle: Option<Handle<Mesh>>,
-    small_mesh_handle: Option<Handle<Mesh>>,
+    materials: HashMap<CanTexture, Handle<StandardMaterial>>,
+    label_materials: HashMap<CanLabel, Handle<StandardMaterial>>,
+    meshes: HashMap<(CanSize, CanCondition), Handle<Mesh>>,
+    label_meshes: HashMap<(CanSize, CanCondition), Handle<Mesh>>,
+    records: HashMap<CanId, CanRecord>,
+    hopper: VecDeque<CanId>, // in pickup order
+    next_id: u32,
 }
 
 impl CanManager {
     pub fn new() -> Self {
         CanManager {
-            dirty_material_handle: None,
-            shiny_material_handle: None,
-            big_mesh_handle: None,
-            small_mesh_handle: None,
+            materials: HashMap::new(),
+            label_materials: HashMap::new(),
+            meshes: HashMap::new(),
+            label_meshes: HashMap::new(),
+            records: HashMap::new(),
+            hopper: VecDeque::new(),
+            next_id: 0,
         }
     }
 
+    /// Returns a can's record.
+    pub fn record(&self, id: CanId) -> &CanRecord {
+        &self.records[&id]
+    }
+
+    /// Returns how many cans are in `state`.
+    pub fn count(&self, state: CanState) -> usize {
+        self.records.values().filter(|r| r.state == state).count()
+    }
+
+    /// Whether there's a can on the sand, at the scene position `slot`.
+    pub fn slot_taken(&self, slot: usize) -> bool {
+        self.records
+            .values()
+            .any(|r| r.state == CanState::OnSand && r.slot == Some(slot))
+    }
+
+    /// Records a can that was just picked up (and despawned).
+    /// Once out of the hopper, it'll be lying on the sand.
+    pub fn store(&mut self, id: CanId) {
+        let record = self.records.get_mut(&id).unwrap();
+        record.data.pose = CanPose::Lying;
+        record.state = CanState::InHopper;
+        record.entity = None;
+        record.slot = None;
+        self.hopper.push_back(id);
+    }
+
+    /// Takes at most `cans` out of the hopper, the first picked up first.
+    pub fn take_from_hopper(&mut self, cans: u8) -> Vec<CanId> {
+        let n = (cans as usize).min(self.hopper.len());
+        self.hopper.drain(..n).collect()
+    }
+
+    /// Records a can that landed in the deposit.
+    pub fn deposit(&mut self, id: CanId) {
+        self.records.get_mut(&id).unwrap().state = CanState::Deposited;
+    }
+
+    /// Records a can that went into the water (and was despawned).
+    pub fn lose(&mut self, id: CanId) {
+        let record = self.records.get_mut(&id).unwrap();
+        record.state = CanState::LostInWater;
+        record.entity = None;
+        record.slot = None;
+    }
+
+    /// Creates a new can's record.
+    fn register(&mut self, data: CanData, slot: Option<usize>) -> CanId {
+        let id = CanId(self.next_id);
+        self.next_id += 1;
+        self.records.insert(
+            id,
+            CanRecord {
+                data,
+                state: CanState::OnSand,
+                entity: None,
+                slot,
+            },
+        );
+        id
+    }
+
     fn load_textures(
         &mut self,
         mut materials: ResMut<Assets<StandardMaterial>>,
         asset_server: Res<AssetServer>,
     ) {
-        self.dirty_material_handle = Some(materials.add(StandardMaterial {
-            base_color_texture: Some(asset_server.load("textures/black_01.png")),
-            reflectance: 0.3,
-            ..default()
-        }));
+        self.materials.insert(
+            CanTexture::Dirty,
+            materials.add(StandardMaterial {
+                base_color_texture: Some(asset_server.load("textures/black_01.png")),
+                reflectance: 0.3,
+                ..default()
+            }),
+        );
 
-        self.shiny_material_handle = Some(materials.add(StandardMaterial {
-            base_color_texture: Some(asset_server.load("textures/black_02.png")),
-            reflectance: 0.34,
-            ..default()
-        }));
+        self.materials.insert(
+            CanTexture::Shiny,
+            materials.add(StandardMaterial {
+                base_color_texture: Some(asset_server.load("textures/black_02.png")),
+                reflectance: 0.34,
+                ..default()
+            }),
+        );
+
+        for label in [
+            CanLabel::Red,
+            CanLabel::Blue,
+            CanLabel::Green,
+            CanLabel::Silver,
+        ] {
+            let material = StandardMaterial {
+                base_color: label.color().unwrap(),
+                reflectance: 0.4,
+                ..default()
+            };
+            self.label_materials.insert(label, materials.add(material));
+        }
     }
 
     fn load_meshes(&mut self, mut meshes: ResMut<Assets<Mesh>>) {
-        self.big_mesh_handle = Some(
-            meshes.add(
-                shape::Cylinder {
-                    radius: 0.15,
-                    height: 0.47,
-                    resolution: 15,
-                    segments: 1,
-                }
-                .into(),
-            ),
-        );
-        self.small_mesh_handle = Some(
-            meshes.add(
-                shape::Cylinder {
-                    radius: 0.15,
-                    height: 0.37,
-                    resolution: 15,
-                    segments: 1,
-                }
-                .into(),
-            ),
-        );
+        for size in CanSize::ALL {
+            for condition in CanCondition::ALL {
+                let can = CanData {
+                    size,
+                    condition,
+                    ..default()
+                };
+                let (radius, height) = can.dimensions();
+
+                self.meshes.insert(
+                    (size, condition),
+                    meshes.add(Self::can_mesh(radius, height, condition)),
+                );
+                // the label covers the middle half of the can
+                self.label_meshes.insert(
+                    (size, condition),
+                    meshes.add(Self::can_mesh(radius * 1.02, height * 0.5, condition)),
+                );
+            }
+        }
     }
 
-    pub fn spawn(&mut self, commands: &mut Commands, can_data: CanData, can_transform: Transform) {
-        let mesh = match can_data.size {
-            CanSize::Big => self.big_mesh_handle.clone().unwrap(),
-            CanSize::Small => self.small_mesh_handle.clone().unwrap(),
-        };
+    /// Cylinder with its sides pushed in, as much as its condition requires.
+    fn can_mesh(radius: f32, height: f32, condition: CanCondition) -> Mesh {
+        let mut mesh: Mesh = shape::Cylinder {
+            radius,
+            height,
+            resolution: 15,
+            segments: 1,
+        }
+        .into();
 
-        let material = match can_data.texture {
-            CanTexture::Shiny => self.shiny_material_handle.clone().unwrap(),
-            CanTexture::Dirty => self.dirty_material_handle.clone().unwrap(),
+        // (how far the dent goes in, whether both sides are dented)
+        let dent = match condition {
+            CanCondition::Intact => None,
+            CanCondition::Dented => Some((0.7, false)),
+            CanCondition::Crushed => Some((0.8, true)),
         };
 
-        commands.spawn((
+        if let (Some((depth, both_sides)), Some(VertexAttributeValues::Float32x3(positions))) =
+            (dent, mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION))
+        {
+            let limit = radius * depth;
+            for p in positions.iter_mut() {
+                if p[0] > limit {
+                    p[0] = limit + (p[0] - limit) * 0.2;
+                } else if both_sides && p[0] < -limit {
+                    p[0] = -limit + (p[0] + limit) * 0.2;
+                }
+            }
+        }
+
+        mesh
+    }
+
+    /// Spawns a new can, returns its entity.
+    /// `slot` is the scene position it's spawned at, if any.
+    pub fn spawn(
+        &mut self,
+        commands: &mut Commands,
+        can_data: CanData,
+        can_transform: Transform,
+        slot: Option<usize>,
+    ) -> Entity {
+        let id = self.register(can_data, slot);
+        self.spawn_entity(commands, id, can_transform)
+    }
+
+    /// Spawns a known can (i.e. one that was in the hopper) back on the sand.
+    pub fn put_back(&mut self, commands: &mut Commands, id: CanId, transform: Transform) -> Entity {
+        self.records.get_mut(&id).unwrap().state = CanState::OnSand;
+        self.spawn_entity(commands, id, transform)
+    }
+
+    fn spawn_entity(
+        &mut self,
+        commands: &mut Commands,
+        id: CanId,
+        can_transform: Transform,
+    ) -> Entity {
+        let can_data = self.records[&id].data;
+        let key = (can_data.size, can_data.condition);
+        let mesh = self.meshes[&key].clone();
+        let material = self.materials[&can_data.texture].clone();
+
+        let mut can = commands.spawn((
             PbrBundle {
                 mesh,
                 material,
@@ -112,7 +480,117 @@ impl CanManager {
                 ..default()
             },
             can_data,
+            id,
+            // so sensors can see it
+            Obstacle::empty()
+                .with_proxy(ObstacleProxy::Shape(can_data.collider()))
+                .with_layers(CollisionLayers::CANS),
         ));
+
+        if let Some(label_material) = self.label_materials.get(&can_data.label) {
+            let label_mesh = self.label_meshes[&key].clone();
+            can.with_children(|parent| {
+                parent.spawn(PbrBundle {
+                    mesh: label_mesh,
+                    material: label_material.clone(),
+                    ..default()
+                });
+            });
+        }
+
+        let entity = can.id();
+        self.records.get_mut(&id).unwrap().entity = Some(entity);
+        entity
+    }
+}
+
+/// When cans are respawned, besides manually (`C` key, or when the AI asks).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RespawnTrigger {
+    Manual,
+    AllCollected,
+    Periodic(f32), // seconds
+}
+
+/// Where cans are respawned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RespawnPlacement {
+    /// Scene's can positions without a can on them.
+    Original,
+    /// Anywhere on the sand.
+    Random,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RespawnPolicy {
+    pub trigger: RespawnTrigger,
+    pub placement: RespawnPlacement,
+}
+
+impl Default for RespawnPolicy {
+    fn default() -> Self {
+        RespawnPolicy {
+            trigger: RespawnTrigger::Manual,
+            placement: RespawnPlacement::Original,
+        }
+    }
+}
+
+/// Decides when cans have to be respawned, following a `RespawnPolicy`.
+#[derive(Resource)]
+pub struct CanRespawner {
+    policy: RespawnPolicy,
+    timer: Timer,
+    requested: bool,
+}
+
+impl CanRespawner {
+    // shortest period between periodic respawns, in seconds
+    const MIN_PERIOD: f32 = 1.0;
+
+    pub fn new(policy: RespawnPolicy) -> Self {
+        let mut respawner = CanRespawner {
+            policy,
+            timer: Timer::default(),
+            requested: false,
+        };
+        respawner.set_policy(policy);
+        respawner
+    }
+
+    pub fn policy(&self) -> RespawnPolicy {
+        self.policy
+    }
+
+    /// Changes the policy, restarting the respawn timer.
+    /// Periods shorter than a second are stretched to one.
+    pub fn set_policy(&mut self, policy: RespawnPolicy) {
+        self.policy = policy;
+        if let RespawnTrigger::Periodic(secs) = policy.trigger {
+            let secs = secs.max(Self::MIN_PERIOD);
+            self.policy.trigger = RespawnTrigger::Periodic(secs);
+            self.timer = Timer::from_seconds(secs, TimerMode::Repeating);
+        }
+    }
+
+    /// Asks for cans to be respawned as soon as possible.
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// Whether it's time to respawn cans, given the time since
+    /// the last call and how many cans are left on the sand.
+    pub fn should_respawn(&mut self, delta: Duration, cans_on_sand: usize) -> bool {
+        let requested = std::mem::take(&mut self.requested);
+
+        match self.policy.trigger {
+            RespawnTrigger::Manual => requested,
+            RespawnTrigger::AllCollected => requested || cans_on_sand == 0,
+            RespawnTrigger::Periodic(_) => {
+                self.timer.tick(delta);
+                requested || self.timer.just_finished()
+            }
+        }
     }
 }
 
@@ -125,3 +603,163 @@ pub fn init_can_manager(
     can_manager.load_meshes(meshes);
     can_manager.load_textures(materials, asset_server);
 }
+
+#[cfg(test)]
+mod can_data_tests {
+    use super::*;
+
+    #[test]
+    fn test_default_can_radius() {
+        let can = CanData::default();
+
+        // big, intact can
+        assert!((can.get_radius() - 0.279).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_radius_follows_variant() {
+        let big = CanData::default();
+        let small = CanData {
+            size: CanSize::Small,
+            ..default()
+        };
+        let crushed = CanData {
+            condition: CanCondition::Crushed,
+            ..default()
+        };
+
+        assert!(small.get_radius() < big.get_radius());
+        assert!(crushed.get_radius() < big.get_radius());
+        assert!(crushed.dimensions().0 > big.dimensions().0);
+    }
+
+    #[test]
+    fn test_placement_rests_on_ground() {
+        let lying = CanData::default();
+        let standing = CanData {
+            pose: CanPose::Standing,
+            ..default()
+        };
+        let half_buried = CanData {
+            pose: CanPose::HalfBuried,
+            ..default()
+        };
+
+        let y = |can: CanData| can.placement(1.0, 2.0, 0.3, 0.5).translation.y;
+        assert!((y(lying) - 0.65).abs() < 0.0001);
+        assert!((y(standing) - 0.735).abs() < 0.0001);
+        assert!((y(half_buried) - 0.5).abs() < 0.0001);
+
+        // standing cans point up
+        let axis = standing
+            .placement(0.0, 0.0, 0.3, 0.0)
+            .rotation
+            .mul_vec3(Vec3::Y);
+        assert!((axis - Vec3::Y).length() < 0.0001);
+    }
+
+    #[test]
+    fn test_default_variety_is_default_can() {
+        let variety = CanVariety::default();
+        let mut rng = variety.rng();
+
+        assert_eq!(variety.sample(&mut rng), CanData::default());
+    }
+
+    #[test]
+    fn test_variety_is_reproducible() {
+        let variety = CanVariety::beach_litter(33);
+        let (mut rng_a, mut rng_b) = (variety.rng(), variety.rng());
+        let a: Vec<CanData> = (0..10).map(|_| variety.sample(&mut rng_a)).collect();
+        let b: Vec<CanData> = (0..10).map(|_| variety.sample(&mut rng_b)).collect();
+
+        assert_eq!(a, b);
+    }
+}
+
+#[cfg(test)]
+mod can_manager_tests {
+    use super::*;
+
+    #[test]
+    fn test_registered_cans_are_on_sand() {
+        let mut manager = CanManager::new();
+        let a = manager.register(CanData::default(), Some(0));
+        let b = manager.register(CanData::default(), None);
+
+        assert_ne!(a, b);
+        assert_eq!(manager.count(CanState::OnSand), 2);
+        assert!(manager.slot_taken(0));
+        assert!(!manager.slot_taken(1));
+    }
+
+    #[test]
+    fn test_stored_can_frees_its_slot() {
+        let mut manager = CanManager::new();
+        let id = manager.register(CanData::default(), Some(3));
+        manager.store(id);
+
+        assert_eq!(manager.record(id).state, CanState::InHopper);
+        assert!(!manager.slot_taken(3));
+    }
+
+    #[test]
+    fn test_hopper_releases_first_picked_up_cans() {
+        let mut manager = CanManager::new();
+        let ids: Vec<CanId> = (0..3)
+            .map(|_| manager.register(CanData::default(), None))
+            .collect();
+        for id in ids.iter().rev() {
+            manager.store(*id);
+        }
+
+        assert_eq!(manager.take_from_hopper(2), vec![ids[2], ids[1]]);
+        assert_eq!(manager.take_from_hopper(5), vec![ids[0]]);
+        assert!(manager.take_from_hopper(1).is_empty());
+    }
+
+    #[test]
+    fn test_manual_respawn_only_when_requested() {
+        let mut respawner = CanRespawner::new(RespawnPolicy::default());
+
+        assert!(!respawner.should_respawn(Duration::from_secs(100), 0));
+        respawner.request();
+        assert!(respawner.should_respawn(Duration::ZERO, 5));
+        assert!(!respawner.should_respawn(Duration::ZERO, 5));
+    }
+
+    #[test]
+    fn test_respawn_when_all_collected() {
+        let mut respawner = CanRespawner::new(RespawnPolicy {
+            trigger: RespawnTrigger::AllCollected,
+            placement: RespawnPlacement::Original,
+        });
+
+        assert!(!respawner.should_respawn(Duration::ZERO, 1));
+        assert!(respawner.should_respawn(Duration::ZERO, 0));
+    }
+
+    #[test]
+    fn test_periodic_respawn() {
+        let mut respawner = CanRespawner::new(RespawnPolicy {
+            trigger: RespawnTrigger::Periodic(10.0),
+            placement: RespawnPlacement::Random,
+        });
+
+        assert!(!respawner.should_respawn(Duration::from_secs(6), 5));
+        assert!(respawner.should_respawn(Duration::from_secs(6), 5));
+        assert!(!respawner.should_respawn(Duration::from_secs(6), 5));
+    }
+
+    #[test]
+    fn test_periodic_respawn_lasts_at_least_a_second() {
+        let mut respawner = CanRespawner::new(RespawnPolicy {
+            trigger: RespawnTrigger::Periodic(0.0),
+            placement: RespawnPlacement::Random,
+        });
+
+        assert_eq!(respawner.policy().trigger, RespawnTrigger::Periodic(1.0));
+        assert!(!respawner.should_respawn(Duration::from_millis(16), 5));
+        assert!(!respawner.should_respawn(Duration::from_millis(16), 5));
+    }
+}
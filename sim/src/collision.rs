@@ -1,25 +1,7 @@
-use bevy::prelude::*;
+use crate::collision_shape::{Collider, ConvexHull};
+use bevy::{prelude::*, utils::HashMap};
 use itertools::Itertools;
 
-pub trait GlobalTransform {
-    fn from_parent(&self, parent: &Transform) -> Self;
-}
-
-impl GlobalTransform for Transform {
-    fn from_parent(&self, parent: &Transform) -> Self {
-        // This isn't correct, but will let it be for now
-
-        let translation = parent.rotation.mul_vec3(self.translation) + parent.translation;
-        let rotation = parent.rotation * self.rotation;
-
-        Transform {
-            translation,
-            rotation,
-            scale: self.scale,
-        }
-    }
-}
-
 #[derive(Copy, Clone)]
 pub struct Line {
     pub org: Vec3,
@@ -50,6 +32,9 @@ impl Plane {
 
 /// Calculates the collision point with the plane formed by triangle abc,
 /// considering current position and rotation.
+///
+/// This is a line, so points behind `line.org` are returned too.
+/// Use `Ray` for anything with a direction (sensors).
 pub fn get_collision_point(line: Line, triangle: Triangle) -> Option<Vec3> {
     const EPSILON: f32 = 0.0000001;
 
@@ -84,6 +69,148 @@ pub fn point_inside_triangle(p: Vec3, triangle: Triangle) -> bool {
     alpha >= 0.0 && beta >= 0.0 && alpha + beta <= 1.0
 }
 
+/// Which triangle faces a ray can hit.
+/// A triangle's front face is the one its normal, (b - a) x (c - a), points to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Culling {
+    /// Both faces are hit.
+    None,
+    /// Only front faces are hit.
+    BackFaces,
+}
+
+/// A half-line, starting at `org` and going (at most `max_dist`) along `dir`.
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub org: Vec3,
+    pub dir: Vec3, // normalized
+    pub max_dist: f32,
+    pub culling: Culling,
+}
+
+/// Where a ray hit a hull.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+    /// Distance from the ray's origin.
+    pub distance: f32,
+    pub point: Vec3,
+    /// Hit triangle's normal, facing the ray's origin.
+    pub normal: Vec3,
+    /// Hit triangle's index within its hull.
+    pub triangle: usize,
+    /// Hull's owner, if known (see `Ray::cast_all`).
+    pub entity: Option<Entity>,
+}
+
+impl Ray {
+    const EPSILON: f32 = 0.0000001;
+
+    pub fn new(org: Vec3, dir: Vec3) -> Self {
+        Ray {
+            org,
+            dir: dir.normalize(),
+            max_dist: f32::INFINITY,
+            culling: Culling::None,
+        }
+    }
+
+    /// Ray along the (world) transform's x axis, the way sensors point.
+    pub fn from_transform(transform: &GlobalTransform) -> Self {
+        Ray::new(
+            transform.translation(),
+            transform.affine().transform_vector3(Vec3::X),
+        )
+    }
+
+    pub fn with_max_distance(mut self, max_dist: f32) -> Self {
+        self.max_dist = max_dist;
+        self
+    }
+
+    pub fn with_culling(mut self, culling: Culling) -> Self {
+        self.culling = culling;
+        self
+    }
+
+    /// Returns the distance to `triangle`, if the ray hits it.
+    /// (Möller-Trumbore intersection)
+    pub fn cast_triangle(&self, triangle: &Triangle) -> Option<f32> {
+        let e1 = triangle.b - triangle.a;
+        let e2 = triangle.c - triangle.a;
+        let p = self.dir.cross(e2);
+        // positive when hitting the front face
+        let det = e1.dot(p);
+
+        let culled = match self.culling {
+            Culling::None => det.abs() < Self::EPSILON,
+            Culling::BackFaces => det < Self::EPSILON,
+        };
+        if culled {
+            return None;
+        }
+
+        let s = self.org - triangle.a;
+        let u = s.dot(p) / det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(e1);
+        let v = self.dir.dot(q) / det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        // hits behind the origin don't count
+        let t = e2.dot(q) / det;
+        if t < 0.0 || t > self.max_dist {
+            None
+        } else {
+            Some(t)
+        }
+    }
+
+    /// Returns the closest hit with `hull`.
+    pub fn cast(&self, hull: &[Triangle]) -> Option<RayHit> {
+        let (triangle, distance) = hull
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| self.cast_triangle(t).map(|d| (i, d)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        let normal = Plane::from_triangle(hull[triangle]).normal;
+        let normal = if normal.dot(self.dir) > 0.0 {
+            -normal
+        } else {
+            normal
+        };
+
+        Some(RayHit {
+            distance,
+            point: self.org + self.dir * distance,
+            normal,
+            triangle,
+            entity: None,
+        })
+    }
+
+    /// Returns the closest hit with any of the entities' hulls.
+    pub fn cast_all<'a>(
+        &self,
+        hulls: impl IntoIterator<Item = (Entity, &'a [Triangle])>,
+    ) -> Option<RayHit> {
+        hulls
+            .into_iter()
+            .filter_map(|(entity, hull)| {
+                self.cast(hull).map(|hit| RayHit {
+                    entity: Some(entity),
+                    ..hit
+                })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+}
+
 /// Distance collision (spherical collision)
 pub trait DistanceCollider {
     fn collides_with_dist(
@@ -125,7 +252,9 @@ pub trait DistanceCollision {
 
 /// Mesh collision (convex hull collision)
 pub trait MeshCollision {
-    fn compute_hull(&self, mesh: &Mesh, transform: &Transform) -> Vec<Triangle> {
+    /// Returns the mesh's triangles in world space, given the entity's
+    /// world transform (scale included).
+    fn compute_hull(&self, mesh: &Mesh, transform: &GlobalTransform) -> Vec<Triangle> {
         // println!("{:?}", mesh.primitive_topology());
 
         let vertices: Vec<Vec3> = mesh
@@ -134,10 +263,7 @@ pub trait MeshCollision {
             .as_float3()
             .unwrap()
             .iter()
-            .map(|s| {
-                let v = transform.rotation.mul_vec3(Vec3::from_array(*s));
-                v + transform.translation
-            })
+            .map(|s| transform.transform_point(Vec3::from_array(*s)))
             .collect();
 
         mesh.indices()
@@ -154,39 +280,386 @@ pub trait MeshCollision {
     }
 }
 
+/// What an obstacle's hull is made of.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObstacleProxy {
+    /// Every triangle in the render mesh.
+    Mesh,
+    /// Convex hull of the render mesh's vertices.
+    ConvexHull,
+    /// A simplified shape, ignoring the render mesh.
+    Shape(Collider),
+}
+
+/// Kinds of collidables, as a bit mask.
+/// Collidables belong to some layers, and sensors only see some of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CollisionLayers(pub u8);
+
+impl CollisionLayers {
+    pub const NONE: Self = CollisionLayers(0);
+    pub const OBSTACLES: Self = CollisionLayers(1);
+    pub const CANS: Self = CollisionLayers(1 << 1);
+    pub const DEPOSIT: Self = CollisionLayers(1 << 2);
+    pub const DEBRIS: Self = CollisionLayers(1 << 3);
+    pub const WATER: Self = CollisionLayers(1 << 4);
+    pub const ALL: Self = CollisionLayers(u8::MAX);
+
+    /// Whether both masks share any layer.
+    pub fn intersects(&self, other: CollisionLayers) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn without(self, other: CollisionLayers) -> Self {
+        CollisionLayers(self.0 & !other.0)
+    }
+}
+
+impl std::ops::BitOr for CollisionLayers {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        CollisionLayers(self.0 | other.0)
+    }
+}
+
 /// The component that allows mesh collision to occur.
 #[derive(Component)]
 pub struct Obstacle {
     pub hull: Vec<Triangle>,
+    pub proxy: ObstacleProxy,
+    pub layers: CollisionLayers,
 }
 
 impl Obstacle {
     pub fn empty() -> Self {
-        Obstacle { hull: Vec::new() }
+        Obstacle {
+            hull: Vec::new(),
+            proxy: ObstacleProxy::Mesh,
+            layers: CollisionLayers::OBSTACLES,
+        }
+    }
+
+    pub fn with_proxy(mut self, proxy: ObstacleProxy) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    pub fn with_layers(mut self, layers: CollisionLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Returns the obstacle's hull in world space, made from its proxy.
+    /// Shapes don't need the render mesh, the rest have no hull without it.
+    pub fn compute_proxy_hull(
+        &self,
+        mesh: Option<&Mesh>,
+        transform: &GlobalTransform,
+    ) -> Vec<Triangle> {
+        match (&self.proxy, mesh) {
+            (ObstacleProxy::Shape(collider), _) => collider.world(transform).triangles(),
+            (_, None) => Vec::new(),
+            (ObstacleProxy::Mesh, Some(mesh)) => self.compute_hull(mesh, transform),
+            (ObstacleProxy::ConvexHull, Some(mesh)) => {
+                let vertices: Vec<Vec3> = self
+                    .compute_hull(mesh, transform)
+                    .iter()
+                    .flat_map(|t| [t.a, t.b, t.c])
+                    .collect();
+                ConvexHull::new(&vertices).map_or(Vec::new(), |hull| hull.triangles())
+            }
+        }
     }
 }
 
 impl MeshCollision for Obstacle {}
 
+// obstacle, with change detection over its mesh and world transform
+type ObstacleHullSource<'a> = (
+    &'a mut Obstacle,
+    Ref<'a, Handle<Mesh>>,
+    Ref<'a, GlobalTransform>,
+);
+
+/// Recomputes obstacles' hulls, only when their transform or mesh changed
+/// (including meshes that just finished loading).
 pub fn compute_hulls(
-    mut obstacles: Query<(&mut Obstacle, &Handle<Mesh>, &Transform)>,
+    mut obstacles: Query<ObstacleHullSource>,
     meshes: Res<Assets<Mesh>>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
 ) {
+    let loaded: Vec<AssetId<Mesh>> = mesh_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Added { id }
+            | AssetEvent::Modified { id }
+            | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
     for (mut obstacle, mesh_handle, transform) in obstacles.iter_mut() {
-        let mesh = meshes.get(mesh_handle);
-        obstacle.hull = match mesh {
-            None => Vec::new(),
-            Some(h) => obstacle.compute_hull(h, transform),
-        };
+        if !transform.is_changed()
+            && !mesh_handle.is_changed()
+            && !loaded.contains(&mesh_handle.id())
+        {
+            continue;
+        }
+
+        let mesh = meshes.get(mesh_handle.as_ref());
+        obstacle.hull = obstacle.compute_proxy_hull(mesh, &transform);
     }
 }
 
-pub trait RayCollider {
-    fn collides_with_mesh(
+/// Uniform grid (over the xz plane) with every obstacle's triangles.
+/// Rays only get tested against the triangles in the cells they go through.
+#[derive(Resource, Default)]
+pub struct ObstacleGrid {
+    // (owner, owner's layers, index within its hull, triangle)
+    triangles: Vec<(Entity, CollisionLayers, usize, Triangle)>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    // cell bounds, (min, max)
+    bounds: Option<((i32, i32), (i32, i32))>,
+}
+
+impl ObstacleGrid {
+    const CELL_SIZE: f32 = 1.0;
+
+    fn cell(x: f32, z: f32) -> (i32, i32) {
+        (
+            (x / Self::CELL_SIZE).floor() as i32,
+            (z / Self::CELL_SIZE).floor() as i32,
+        )
+    }
+
+    /// Replaces the grid's contents with the given hulls.
+    pub fn rebuild<'a>(
         &mut self,
-        self_transform: &Transform,
-        object_hull: &Vec<Triangle>,
-    ) -> bool;
+        hulls: impl IntoIterator<Item = (Entity, CollisionLayers, &'a [Triangle])>,
+    ) {
+        self.triangles.clear();
+        self.cells.clear();
+        self.bounds = None;
+
+        for (entity, layers, hull) in hulls {
+            for (i, triangle) in hull.iter().enumerate() {
+                let index = self.triangles.len();
+                self.triangles.push((entity, layers, i, *triangle));
+
+                let min = triangle.a.min(triangle.b).min(triangle.c);
+                let max = triangle.a.max(triangle.b).max(triangle.c);
+                let (min, max) = (Self::cell(min.x, min.z), Self::cell(max.x, max.z));
+
+                for cx in min.0..=max.0 {
+                    for cz in min.1..=max.1 {
+                        self.cells.entry((cx, cz)).or_default().push(index);
+                    }
+                }
+
+                self.bounds = Some(match self.bounds {
+                    None => (min, max),
+                    Some((bmin, bmax)) => (
+                        (bmin.0.min(min.0), bmin.1.min(min.1)),
+                        (bmax.0.max(max.0), bmax.1.max(max.1)),
+                    ),
+                });
+            }
+        }
+    }
+
+    /// Returns the cells `ray` goes through, in order, until it reaches
+    /// its max distance or leaves the grid. (Amanatides-Woo traversal)
+    fn cells_along(&self, ray: &Ray) -> Vec<(i32, i32)> {
+        let Some((min, max)) = self.bounds else {
+            return Vec::new();
+        };
+
+        // whether `c` is out of the grid and won't come back
+        let leaving = |c: i32, step: i32, min: i32, max: i32| {
+            (c > max && step >= 0) || (c < min && step <= 0)
+        };
+
+        // (current cell, step, distance to next cell boundary, distance between boundaries)
+        let axis = |org: f32, dir: f32| {
+            let c = (org / Self::CELL_SIZE).floor() as i32;
+            if dir == 0.0 {
+                return (c, 0, f32::INFINITY, f32::INFINITY);
+            }
+
+            let step = dir.signum() as i32;
+            let boundary = (c + (step > 0) as i32) as f32 * Self::CELL_SIZE;
+            (c, step, (boundary - org) / dir, Self::CELL_SIZE / dir.abs())
+        };
+
+        let (mut cx, step_x, mut t_max_x, t_delta_x) = axis(ray.org.x, ray.dir.x);
+        let (mut cz, step_z, mut t_max_z, t_delta_z) = axis(ray.org.z, ray.dir.z);
+        let mut cells = Vec::new();
+
+        // vertical rays stay in their cell (the chosen t_max is infinite)
+        loop {
+            if leaving(cx, step_x, min.0, max.0) || leaving(cz, step_z, min.1, max.1) {
+                break;
+            }
+            cells.push((cx, cz));
+
+            if t_max_x < t_max_z {
+                if !t_max_x.is_finite() || t_max_x > ray.max_dist {
+                    break;
+                }
+                cx += step_x;
+                t_max_x += t_delta_x;
+            } else {
+                if !t_max_z.is_finite() || t_max_z > ray.max_dist {
+                    break;
+                }
+                cz += step_z;
+                t_max_z += t_delta_z;
+            }
+        }
+
+        cells
+    }
+
+    /// Returns the closest obstacle hit by `ray`.
+    pub fn cast(&self, ray: &Ray) -> Option<RayHit> {
+        self.cast_layers(ray, CollisionLayers::ALL)
+    }
+
+    /// Returns the closest hit by `ray`, among obstacles in any of `layers`.
+    pub fn cast_layers(&self, ray: &Ray, layers: CollisionLayers) -> Option<RayHit> {
+        let mut candidates: Vec<usize> = self
+            .cells_along(ray)
+            .iter()
+            .filter_map(|cell| self.cells.get(cell))
+            .flatten()
+            .copied()
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let (index, distance) = candidates
+            .into_iter()
+            .filter(|i| layers.intersects(self.triangles[*i].1))
+            .filter_map(|i| ray.cast_triangle(&self.triangles[i].3).map(|d| (i, d)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        let (entity, _, triangle, _) = self.triangles[index];
+        let hit = ray.cast(&[self.triangles[index].3])?;
+        Some(RayHit {
+            distance,
+            triangle,
+            entity: Some(entity),
+            ..hit
+        })
+    }
+}
+
+/// Rebuilds the `ObstacleGrid` whenever an obstacle's hull changes.
+pub fn update_obstacle_grid(
+    mut grid: ResMut<ObstacleGrid>,
+    obstacles: Query<(Entity, Ref<Obstacle>)>,
+    mut removed: RemovedComponents<Obstacle>,
+) {
+    let removed = removed.read().count() > 0;
+    if !removed && !obstacles.iter().any(|(_, obstacle)| obstacle.is_changed()) {
+        return;
+    }
+
+    grid.rebuild(obstacles.iter().map(|(entity, obstacle)| {
+        let obstacle = obstacle.into_inner();
+        (entity, obstacle.layers, obstacle.hull.as_slice())
+    }));
+}
+
+#[cfg(test)]
+mod world_transform_tests {
+    use super::*;
+    use crate::collision_shape::CollisionShape;
+
+    fn extents(hull: &[Triangle]) -> (Vec3, Vec3) {
+        hull.iter()
+            .flat_map(|t| [t.a, t.b, t.c])
+            .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), p| {
+                (min.min(p), max.max(p))
+            })
+    }
+
+    fn assert_similar(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 0.0001, "{:?} not similar to {:?}", a, b);
+    }
+
+    #[test]
+    fn test_hull_of_scaled_obstacle() {
+        let mesh = Mesh::from(shape::Cube { size: 1.0 });
+        let transform = Transform::from_xyz(5.0, 0.0, 0.0).with_scale(Vec3::new(2.0, 1.0, 3.0));
+        let hull = Obstacle::empty().compute_hull(&mesh, &transform.into());
+
+        let (min, max) = extents(&hull);
+        assert_similar(min, Vec3::new(4.0, -0.5, -1.5));
+        assert_similar(max, Vec3::new(6.0, 0.5, 1.5));
+    }
+
+    #[test]
+    fn test_hull_of_scaled_and_rotated_obstacle() {
+        let mesh = Mesh::from(shape::Cube { size: 1.0 });
+        let transform = Transform::from_scale(Vec3::new(4.0, 1.0, 1.0))
+            .with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2));
+        let hull = Obstacle::empty().compute_hull(&mesh, &transform.into());
+
+        // scale is applied before rotation, so the long side ends up along z
+        let (min, max) = extents(&hull);
+        assert_similar(min, Vec3::new(-0.5, -0.5, -2.0));
+        assert_similar(max, Vec3::new(0.5, 0.5, 2.0));
+    }
+
+    #[test]
+    fn test_ray_hits_scaled_obstacle() {
+        let mesh = Mesh::from(shape::Cube { size: 1.0 });
+        let transform = Transform::from_xyz(5.0, 0.0, 0.0).with_scale(Vec3::splat(2.0));
+        let hull = Obstacle::empty().compute_hull(&mesh, &transform.into());
+        let hit = Ray::new(Vec3::ZERO, Vec3::X).cast(&hull).unwrap();
+
+        assert!((hit.distance - 4.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_proxy_hulls() {
+        let mesh = Mesh::from(shape::Cube { size: 1.0 });
+        let transform = GlobalTransform::from(Transform::from_xyz(5.0, 0.0, 0.0));
+
+        let convex = Obstacle::empty()
+            .with_proxy(ObstacleProxy::ConvexHull)
+            .compute_proxy_hull(Some(&mesh), &transform);
+        assert_eq!(convex.len(), 12);
+
+        // shapes ignore the render mesh
+        let sphere = Obstacle::empty()
+            .with_proxy(ObstacleProxy::Shape(Collider::new(
+                CollisionShape::Sphere { radius: 2.0 },
+            )))
+            .compute_proxy_hull(None, &transform);
+        let (min, max) = extents(&sphere);
+        assert_similar(min, Vec3::new(3.0, -2.0, -2.0));
+        assert_similar(max, Vec3::new(7.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn test_ray_from_nested_transform() {
+        // sensor, mounted on a rotated and scaled part of a moved parent
+        let parent = GlobalTransform::from(
+            Transform::from_xyz(1.0, 0.0, 0.0)
+                .with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)),
+        );
+        let part = Transform::from_xyz(0.0, 1.0, 0.0).with_scale(Vec3::splat(2.0));
+        let sensor = Transform::from_xyz(0.5, 0.0, 0.0);
+        let world = parent * part * sensor;
+        let ray = Ray::from_transform(&world);
+
+        // part's scale doubles the sensor's offset, parent turns it to -z
+        assert_similar(ray.org, Vec3::new(1.0, 1.0, -1.0));
+        assert_similar(ray.dir, Vec3::NEG_Z);
+    }
 }
 
 #[cfg(test)]
@@ -236,6 +709,130 @@ mod geometric_primitive_tests {
     }
 }
 
+#[cfg(test)]
+mod obstacle_grid_tests {
+    use super::*;
+
+    // a 1x1 wall facing -x, at x, centered at z
+    fn wall(x: f32, z: f32) -> Vec<Triangle> {
+        vec![
+            Triangle {
+                a: Vec3::new(x, 0.0, z - 0.5),
+                b: Vec3::new(x, 0.0, z + 0.5),
+                c: Vec3::new(x, 1.0, z + 0.5),
+            },
+            Triangle {
+                a: Vec3::new(x, 0.0, z - 0.5),
+                b: Vec3::new(x, 1.0, z + 0.5),
+                c: Vec3::new(x, 1.0, z - 0.5),
+            },
+        ]
+    }
+
+    fn grid(hulls: &[(Entity, Vec<Triangle>)]) -> ObstacleGrid {
+        let mut grid = ObstacleGrid::default();
+        grid.rebuild(
+            hulls
+                .iter()
+                .map(|(e, h)| (*e, CollisionLayers::OBSTACLES, h.as_slice())),
+        );
+        grid
+    }
+
+    #[test]
+    fn test_empty_grid_has_no_hits() {
+        let ray = Ray::new(Vec3::ZERO, Vec3::X);
+
+        assert_eq!(ObstacleGrid::default().cast(&ray), None);
+    }
+
+    #[test]
+    fn test_grid_hits_far_obstacle() {
+        let e = Entity::from_raw(7);
+        let grid = grid(&[(e, wall(10.3, -4.2))]);
+        let ray = Ray::new(Vec3::new(0.0, 0.5, 0.0), Vec3::new(10.3, 0.0, -4.2));
+        let hit = grid.cast(&ray).unwrap();
+
+        assert_eq!(hit.entity, Some(e));
+        assert!((hit.point - Vec3::new(10.3, 0.5, -4.2)).length() < 0.0001);
+    }
+
+    #[test]
+    fn test_grid_filters_layers() {
+        let (near, far) = (Entity::from_raw(1), Entity::from_raw(2));
+        let (near_wall, far_wall) = (wall(2.0, 0.0), wall(4.0, 0.0));
+        let mut grid = ObstacleGrid::default();
+        grid.rebuild([
+            (near, CollisionLayers::CANS, near_wall.as_slice()),
+            (far, CollisionLayers::OBSTACLES, far_wall.as_slice()),
+        ]);
+        let ray = Ray::new(Vec3::new(0.0, 0.5, 0.0), Vec3::X);
+
+        assert_eq!(grid.cast(&ray).unwrap().entity, Some(near));
+        let mask = CollisionLayers::ALL.without(CollisionLayers::CANS);
+        assert_eq!(grid.cast_layers(&ray, mask).unwrap().entity, Some(far));
+        assert_eq!(grid.cast_layers(&ray, CollisionLayers::NONE), None);
+    }
+
+    #[test]
+    fn test_grid_ray_pointing_away() {
+        let grid = grid(&[(Entity::from_raw(0), wall(3.0, 0.0))]);
+        let ray = Ray::new(Vec3::new(0.0, 0.5, 0.0), Vec3::NEG_X);
+
+        assert_eq!(grid.cast(&ray), None);
+    }
+
+    #[test]
+    fn test_grid_respects_max_distance() {
+        let grid = grid(&[(Entity::from_raw(0), wall(3.0, 0.0))]);
+        let ray = Ray::new(Vec3::new(0.0, 0.5, 0.0), Vec3::X);
+
+        assert_eq!(grid.cast(&ray.with_max_distance(2.5)), None);
+        assert_eq!(
+            grid.cast(&ray.with_max_distance(3.5)).unwrap().distance,
+            3.0
+        );
+    }
+
+    #[test]
+    fn test_grid_vertical_ray_ends() {
+        let roof = vec![Triangle {
+            a: Vec3::new(-1.0, 2.0, -1.0),
+            b: Vec3::new(1.0, 2.0, -1.0),
+            c: Vec3::new(0.0, 2.0, 1.0),
+        }];
+        let grid = grid(&[
+            (Entity::from_raw(0), wall(3.0, 0.0)),
+            (Entity::from_raw(1), roof),
+        ]);
+
+        // no max distance, the ray only ever sees its own cell
+        let up = grid.cast(&Ray::new(Vec3::new(0.0, 0.5, 0.0), Vec3::Y));
+        assert_eq!(up.unwrap().entity, Some(Entity::from_raw(1)));
+        assert_eq!(
+            grid.cast(&Ray::new(Vec3::new(0.0, 0.5, 0.0), Vec3::NEG_Y)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_grid_matches_brute_force() {
+        let hulls = vec![
+            (Entity::from_raw(0), wall(3.0, 0.0)),
+            (Entity::from_raw(1), wall(1.5, 0.2)),
+            (Entity::from_raw(2), wall(-2.0, 1.0)),
+        ];
+        let grid = grid(&hulls);
+
+        for dir in [Vec3::X, Vec3::NEG_X, Vec3::new(-2.0, 0.0, 1.0), Vec3::Z] {
+            let ray = Ray::new(Vec3::new(0.0, 0.5, 0.0), dir);
+            let brute = ray.cast_all(hulls.iter().map(|(e, h)| (*e, h.as_slice())));
+
+            assert_eq!(grid.cast(&ray), brute);
+        }
+    }
+}
+
 #[cfg(test)]
 mod distance_collision_tests {
     use super::*;
@@ -338,6 +935,82 @@ mod geometry_functions_tests {
         assert_eq!(get_collision_point(line, triangle), None)
     }
 
+    // faces -x, at x = 1
+    fn wall() -> Triangle {
+        Triangle {
+            a: Vec3::new(1.0, -1.0, -1.0),
+            b: Vec3::new(1.0, -1.0, 1.0),
+            c: Vec3::new(1.0, 1.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn test_ray_hits_triangle_in_front() {
+        let ray = Ray::new(Vec3::ZERO, Vec3::X);
+        let hit = ray.cast(&[wall()]).unwrap();
+
+        assert_eq!(hit.distance, 1.0);
+        assert_eq!(hit.point, Vec3::X);
+        assert_eq!(hit.normal, Vec3::NEG_X);
+        assert_eq!(hit.triangle, 0);
+        assert_eq!(hit.entity, None);
+    }
+
+    #[test]
+    fn test_ray_pointing_away_doesnt_hit() {
+        let ray = Ray::new(Vec3::ZERO, Vec3::NEG_X);
+
+        assert_eq!(ray.cast(&[wall()]), None);
+    }
+
+    #[test]
+    fn test_ray_starting_past_triangle_doesnt_hit() {
+        let ray = Ray::new(Vec3::new(2.0, 0.0, 0.0), Vec3::X);
+
+        assert_eq!(ray.cast(&[wall()]), None);
+    }
+
+    #[test]
+    fn test_ray_max_distance() {
+        let ray = Ray::new(Vec3::ZERO, Vec3::X);
+
+        assert_eq!(ray.with_max_distance(0.9).cast(&[wall()]), None);
+        assert!(ray.with_max_distance(1.1).cast(&[wall()]).is_some());
+    }
+
+    #[test]
+    fn test_ray_back_face_culling() {
+        let ray = Ray::new(Vec3::new(2.0, 0.0, 0.0), Vec3::NEG_X);
+        let hit = ray.cast(&[wall()]).unwrap();
+
+        // hit from behind, normal still faces the ray
+        assert_eq!(hit.normal, Vec3::X);
+        assert_eq!(ray.with_culling(Culling::BackFaces).cast(&[wall()]), None);
+        assert!(Ray::new(Vec3::ZERO, Vec3::X)
+            .with_culling(Culling::BackFaces)
+            .cast(&[wall()])
+            .is_some());
+    }
+
+    #[test]
+    fn test_ray_returns_closest_hit() {
+        let mut far = wall();
+        far.a.x = 3.0;
+        far.b.x = 3.0;
+        far.c.x = 3.0;
+        let ray = Ray::new(Vec3::ZERO, Vec3::X);
+
+        assert_eq!(ray.cast(&[far, wall()]).unwrap().triangle, 1);
+
+        let (near_entity, far_entity) = (Entity::from_raw(1), Entity::from_raw(2));
+        let hulls = [(far_entity, vec![far]), (near_entity, vec![wall()])];
+        let hit = ray
+            .cast_all(hulls.iter().map(|(e, h)| (*e, h.as_slice())))
+            .unwrap();
+        assert_eq!(hit.entity, Some(near_entity));
+        assert_eq!(hit.distance, 1.0);
+    }
+
     #[test]
     fn test_point_inside_triangle_1() {
         let triangle = Triangle {
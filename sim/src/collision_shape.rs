@@ -0,0 +1,1088 @@
+use crate::collision::{Ray, RayHit, Triangle};
+use bevy::prelude::*;
+
+/// Simplified collision shapes, used as proxies instead of render meshes.
+///
+/// Shapes are described in their entity's local space, and placed in
+/// the world with `Collider::world`. Cylinders and capsules are
+/// aligned with the local y axis.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CollisionShape {
+    /// Box that stays aligned with the world's axes, whatever the rotation
+    /// (it grows to contain the rotated box).
+    Aabb {
+        half_extents: Vec3,
+    },
+    /// Box that rotates with its entity.
+    Obb {
+        half_extents: Vec3,
+    },
+    Sphere {
+        radius: f32,
+    },
+    Capsule {
+        radius: f32,
+        half_length: f32,
+    },
+    Cylinder {
+        radius: f32,
+        half_height: f32,
+    },
+    ConvexHull(ConvexHull),
+}
+
+/// A collision shape, offset from its entity.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collider {
+    pub shape: CollisionShape,
+    pub offset: Transform,
+}
+
+impl Collider {
+    pub fn new(shape: CollisionShape) -> Self {
+        Collider {
+            shape,
+            offset: Transform::IDENTITY,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Transform) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Places the shape in the world, given its entity's world transform.
+    /// Scale is applied to the shape's dimensions (round shapes take the
+    /// largest scale of their round axes).
+    pub fn world(&self, transform: &GlobalTransform) -> WorldShape {
+        let (scale, rotation, center) = (*transform * self.offset).to_scale_rotation_translation();
+        let round_scale = scale.x.max(scale.z);
+
+        match &self.shape {
+            CollisionShape::Aabb { half_extents } => {
+                let rotation = Mat3::from_quat(rotation);
+                let he = Mat3::from_cols(
+                    rotation.x_axis.abs(),
+                    rotation.y_axis.abs(),
+                    rotation.z_axis.abs(),
+                ) * (*half_extents * scale);
+                WorldShape::Aabb {
+                    min: center - he,
+                    max: center + he,
+                }
+            }
+            CollisionShape::Obb { half_extents } => WorldShape::Obb {
+                center,
+                rotation,
+                half_extents: *half_extents * scale,
+            },
+            CollisionShape::Sphere { radius } => WorldShape::Sphere {
+                center,
+                radius: radius * scale.max_element(),
+            },
+            CollisionShape::Capsule {
+                radius,
+                half_length,
+            } => WorldShape::Capsule {
+                center,
+                rotation,
+                radius: radius * round_scale,
+                half_length: half_length * scale.y,
+            },
+            CollisionShape::Cylinder {
+                radius,
+                half_height,
+            } => WorldShape::Cylinder {
+                center,
+                rotation,
+                radius: radius * round_scale,
+                half_height: half_height * scale.y,
+            },
+            CollisionShape::ConvexHull(hull) => {
+                let affine = transform.affine() * self.offset.compute_affine();
+                let points: Vec<Vec3> = hull
+                    .points
+                    .iter()
+                    .map(|p| affine.transform_point3(*p))
+                    .collect();
+                WorldShape::ConvexHull(ConvexHull {
+                    points,
+                    faces: hull.faces.clone(),
+                })
+            }
+        }
+    }
+}
+
+impl Collider {
+    /// Returns the collider's path from `from` to `to`.
+    pub fn sweep(&self, from: &GlobalTransform, to: &GlobalTransform) -> Sweep {
+        let (from_scale, from_rot, from_pos) = from.to_scale_rotation_translation();
+        let (to_scale, to_rot, to_pos) = to.to_scale_rotation_translation();
+        let n = (from_rot.angle_between(to_rot) / Sweep::MAX_STEP_ANGLE)
+            .ceil()
+            .max(1.0) as usize;
+
+        let shapes: Vec<WorldShape> = (0..=n)
+            .map(|i| {
+                let k = i as f32 / n as f32;
+                let pose = Transform {
+                    translation: from_pos.lerp(to_pos, k),
+                    rotation: from_rot.slerp(to_rot, k),
+                    scale: from_scale.lerp(to_scale, k),
+                };
+                self.world(&pose.into())
+            })
+            .collect();
+
+        let steps = shapes
+            .windows(2)
+            .map(|w| (w[0].clone(), w[1].center() - w[0].center()))
+            .collect();
+
+        Sweep { steps }
+    }
+}
+
+/// Something that declares a simplified collision shape.
+pub trait HasCollider {
+    fn collider(&self) -> Collider;
+}
+
+/// A collision shape, placed in the world.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WorldShape {
+    Aabb {
+        min: Vec3,
+        max: Vec3,
+    },
+    Obb {
+        center: Vec3,
+        rotation: Quat,
+        half_extents: Vec3,
+    },
+    Sphere {
+        center: Vec3,
+        radius: f32,
+    },
+    Capsule {
+        center: Vec3,
+        rotation: Quat,
+        radius: f32,
+        half_length: f32,
+    },
+    Cylinder {
+        center: Vec3,
+        rotation: Quat,
+        radius: f32,
+        half_height: f32,
+    },
+    ConvexHull(ConvexHull),
+}
+
+impl WorldShape {
+    const EPSILON: f32 = 0.000001;
+    const GJK_MAX_ITERATIONS: usize = 64;
+
+    /// A single point, as a shape.
+    pub fn point(p: Vec3) -> Self {
+        WorldShape::Sphere {
+            center: p,
+            radius: 0.0,
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        match self {
+            WorldShape::Aabb { min, max } => (*min + *max) / 2.0,
+            WorldShape::Obb { center, .. }
+            | WorldShape::Sphere { center, .. }
+            | WorldShape::Capsule { center, .. }
+            | WorldShape::Cylinder { center, .. } => *center,
+            WorldShape::ConvexHull(hull) => {
+                hull.points.iter().copied().sum::<Vec3>() / hull.points.len() as f32
+            }
+        }
+    }
+
+    /// Returns the shape's furthest point along `dir`.
+    pub fn support(&self, dir: Vec3) -> Vec3 {
+        match self {
+            WorldShape::Aabb { min, max } => Vec3::select(dir.cmpge(Vec3::ZERO), *max, *min),
+            WorldShape::Obb {
+                center,
+                rotation,
+                half_extents,
+            } => {
+                let local = rotation.inverse() * dir;
+                *center + *rotation * (local.signum() * *half_extents)
+            }
+            WorldShape::Sphere { center, radius } => *center + dir.normalize_or_zero() * *radius,
+            WorldShape::Capsule {
+                center,
+                rotation,
+                radius,
+                half_length,
+            } => {
+                let axis = *rotation * Vec3::Y;
+                *center
+                    + axis * axis.dot(dir).signum() * *half_length
+                    + dir.normalize_or_zero() * *radius
+            }
+            WorldShape::Cylinder {
+                center,
+                rotation,
+                radius,
+                half_height,
+            } => {
+                let axis = *rotation * Vec3::Y;
+                let along = axis.dot(dir);
+                let across = (dir - axis * along).normalize_or_zero();
+                *center + axis * along.signum() * *half_height + across * *radius
+            }
+            WorldShape::ConvexHull(hull) => hull
+                .points
+                .iter()
+                .copied()
+                .max_by(|a, b| a.dot(dir).total_cmp(&b.dot(dir)))
+                .unwrap_or(Vec3::ZERO),
+        }
+    }
+
+    /// Checks whether both shapes overlap.
+    /// Spheres and AABBs are compared directly, everything else goes
+    /// through GJK (all shapes are convex).
+    pub fn intersects(&self, other: &WorldShape) -> bool {
+        match (self, other) {
+            (
+                WorldShape::Sphere {
+                    center: a,
+                    radius: ra,
+                },
+                WorldShape::Sphere {
+                    center: b,
+                    radius: rb,
+                },
+            ) => a.distance(*b) <= ra + rb,
+            (
+                WorldShape::Aabb {
+                    min: amin,
+                    max: amax,
+                },
+                WorldShape::Aabb {
+                    min: bmin,
+                    max: bmax,
+                },
+            ) => amin.cmple(*bmax).all() && bmin.cmple(*amax).all(),
+            _ => Self::gjk(self, other),
+        }
+    }
+
+    /// GJK intersection test: the shapes overlap if their Minkowski
+    /// difference contains the origin.
+    pub fn gjk(a: &impl Convex, b: &impl Convex) -> bool {
+        let support = |d: Vec3| a.support(d) - b.support(-d);
+
+        let mut dir = a.center() - b.center();
+        if dir.length_squared() < Self::EPSILON {
+            dir = Vec3::X;
+        }
+
+        let first = support(dir);
+        let mut simplex = vec![first];
+        dir = -first;
+
+        for _ in 0..Self::GJK_MAX_ITERATIONS {
+            if dir.length_squared() < Self::EPSILON {
+                // origin is on the simplex
+                return true;
+            }
+
+            let p = support(dir);
+            if p.dot(dir) < 0.0 {
+                return false;
+            }
+
+            simplex.push(p);
+            if Self::next_simplex(&mut simplex, &mut dir) {
+                return true;
+            }
+        }
+
+        // didn't converge, the shapes are (at most) touching
+        true
+    }
+
+    /// Keeps the simplex' feature closest to the origin, and points `dir`
+    /// to the origin from it. Returns true if the simplex contains the origin.
+    /// The last point in `simplex` is the newest.
+    fn next_simplex(simplex: &mut Vec<Vec3>, dir: &mut Vec3) -> bool {
+        match simplex.len() {
+            2 => {
+                let (b, a) = (simplex[0], simplex[1]);
+                Self::line_case(simplex, dir, a, b)
+            }
+            3 => {
+                let (c, b, a) = (simplex[0], simplex[1], simplex[2]);
+                Self::triangle_case(simplex, dir, a, b, c)
+            }
+            _ => {
+                let (d, c, b, a) = (simplex[0], simplex[1], simplex[2], simplex[3]);
+                let ao = -a;
+                let (ab, ac, ad) = (b - a, c - a, d - a);
+
+                if ab.cross(ac).dot(ao) > 0.0 {
+                    Self::triangle_case(simplex, dir, a, b, c)
+                } else if ac.cross(ad).dot(ao) > 0.0 {
+                    Self::triangle_case(simplex, dir, a, c, d)
+                } else if ad.cross(ab).dot(ao) > 0.0 {
+                    Self::triangle_case(simplex, dir, a, d, b)
+                } else {
+                    true
+                }
+            }
+        }
+    }
+
+    fn line_case(simplex: &mut Vec<Vec3>, dir: &mut Vec3, a: Vec3, b: Vec3) -> bool {
+        let (ab, ao) = (b - a, -a);
+
+        if ab.dot(ao) > 0.0 {
+            *simplex = vec![b, a];
+            *dir = ab.cross(ao).cross(ab);
+            // origin on the segment
+            dir.length_squared() < Self::EPSILON
+        } else {
+            *simplex = vec![a];
+            *dir = ao;
+            false
+        }
+    }
+
+    fn triangle_case(simplex: &mut Vec<Vec3>, dir: &mut Vec3, a: Vec3, b: Vec3, c: Vec3) -> bool {
+        let (ab, ac, ao) = (b - a, c - a, -a);
+        let abc = ab.cross(ac);
+
+        if abc.cross(ac).dot(ao) > 0.0 {
+            if ac.dot(ao) > 0.0 {
+                *simplex = vec![c, a];
+                *dir = ac.cross(ao).cross(ac);
+                dir.length_squared() < Self::EPSILON
+            } else {
+                Self::line_case(simplex, dir, a, b)
+            }
+        } else if ab.cross(abc).dot(ao) > 0.0 {
+            Self::line_case(simplex, dir, a, b)
+        } else {
+            let side = abc.dot(ao);
+            if side.abs() < Self::EPSILON {
+                // origin on the triangle
+                return true;
+            }
+
+            // keep the winding so the origin is in front of the triangle
+            if side > 0.0 {
+                *simplex = vec![c, b, a];
+                *dir = abc;
+            } else {
+                *simplex = vec![b, c, a];
+                *dir = -abc;
+            }
+            false
+        }
+    }
+
+    /// Returns where `ray` first gets through the shape's surface.
+    /// Rays starting inside the shape hit it on their way out.
+    pub fn cast(&self, ray: &Ray) -> Option<RayHit> {
+        let (distance, normal) = match self {
+            WorldShape::Aabb { min, max } => {
+                let center = (*min + *max) / 2.0;
+                Self::local_cast(ray, center, Quat::IDENTITY, |org, dir| {
+                    Self::box_cast(org, dir, (*max - *min) / 2.0)
+                })
+            }
+            WorldShape::Obb {
+                center,
+                rotation,
+                half_extents,
+            } => Self::local_cast(ray, *center, *rotation, |org, dir| {
+                Self::box_cast(org, dir, *half_extents)
+            }),
+            WorldShape::Sphere { center, radius } => {
+                Self::local_cast(ray, *center, Quat::IDENTITY, |org, dir| {
+                    Self::sphere_cast(org, dir, *radius)
+                })
+            }
+            WorldShape::Cylinder {
+                center,
+                rotation,
+                radius,
+                half_height,
+            } => Self::local_cast(ray, *center, *rotation, |org, dir| {
+                let caps = [-1.0, 1.0].into_iter().filter_map(|side: f32| {
+                    if dir.y.abs() < Self::EPSILON {
+                        return None;
+                    }
+                    let t = (side * half_height - org.y) / dir.y;
+                    let p = org + dir * t;
+                    (t >= 0.0 && p.x * p.x + p.z * p.z <= radius * radius)
+                        .then_some((t, Vec3::Y * side))
+                });
+
+                Self::tube_cast(org, dir, *radius, *half_height)
+                    .into_iter()
+                    .chain(caps)
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+            }),
+            WorldShape::Capsule {
+                center,
+                rotation,
+                radius,
+                half_length,
+            } => Self::local_cast(ray, *center, *rotation, |org, dir| {
+                let ends = [-1.0, 1.0].into_iter().filter_map(|side: f32| {
+                    let end = Vec3::Y * side * *half_length;
+                    Self::sphere_cast(org - end, dir, *radius)
+                });
+
+                Self::tube_cast(org, dir, *radius, *half_length)
+                    .into_iter()
+                    .chain(ends)
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+            }),
+            WorldShape::ConvexHull(hull) => ray
+                .cast(&hull.triangles())
+                .map(|hit| (hit.distance, hit.normal)),
+        }?;
+
+        if distance > ray.max_dist {
+            return None;
+        }
+
+        // normal faces the ray's origin
+        let normal = if normal.dot(ray.dir) > 0.0 {
+            -normal
+        } else {
+            normal
+        };
+
+        Some(RayHit {
+            distance,
+            point: ray.org + ray.dir * distance,
+            normal,
+            triangle: 0,
+            entity: None,
+        })
+    }
+
+    /// Casts `ray` in the shape's local space (given by `center` and `rotation`),
+    /// with `cast` returning the local distance and normal.
+    fn local_cast(
+        ray: &Ray,
+        center: Vec3,
+        rotation: Quat,
+        cast: impl Fn(Vec3, Vec3) -> Option<(f32, Vec3)>,
+    ) -> Option<(f32, Vec3)> {
+        let inverse = rotation.inverse();
+        let (t, normal) = cast(inverse * (ray.org - center), inverse * ray.dir)?;
+        Some((t, rotation * normal))
+    }
+
+    /// Ray against a box centered at the origin. (slab method)
+    fn box_cast(org: Vec3, dir: Vec3, half_extents: Vec3) -> Option<(f32, Vec3)> {
+        let inv = dir.recip();
+        let t1 = (-half_extents - org) * inv;
+        let t2 = (half_extents - org) * inv;
+        let (near, far) = (t1.min(t2), t1.max(t2));
+        let (t_near, t_far) = (near.max_element(), far.min_element());
+
+        if t_near > t_far || t_far < 0.0 {
+            return None;
+        }
+
+        // entering, or leaving if the ray starts inside
+        let (t, planes) = if t_near >= 0.0 {
+            (t_near, near)
+        } else {
+            (t_far, far)
+        };
+        let axis = if planes.x == t {
+            Vec3::X
+        } else if planes.y == t {
+            Vec3::Y
+        } else {
+            Vec3::Z
+        };
+
+        Some((t, axis * (org + dir * t).dot(axis).signum()))
+    }
+
+    /// Ray against a sphere centered at the origin.
+    fn sphere_cast(org: Vec3, dir: Vec3, radius: f32) -> Option<(f32, Vec3)> {
+        let b = org.dot(dir);
+        let c = org.length_squared() - radius * radius;
+        let disc = b * b - c;
+        if disc < 0.0 {
+            return None;
+        }
+
+        let sq = disc.sqrt();
+        let t = if -b - sq >= 0.0 { -b - sq } else { -b + sq };
+        if t < 0.0 {
+            return None;
+        }
+
+        Some((t, (org + dir * t).normalize()))
+    }
+
+    /// Ray against the side of a y aligned cylinder, centered at the origin.
+    fn tube_cast(org: Vec3, dir: Vec3, radius: f32, half_height: f32) -> Option<(f32, Vec3)> {
+        let a = dir.x * dir.x + dir.z * dir.z;
+        if a < Self::EPSILON {
+            return None;
+        }
+
+        let b = org.x * dir.x + org.z * dir.z;
+        let c = org.x * org.x + org.z * org.z - radius * radius;
+        let disc = b * b - a * c;
+        if disc < 0.0 {
+            return None;
+        }
+
+        let sq = disc.sqrt();
+        [(-b - sq) / a, (-b + sq) / a]
+            .into_iter()
+            .filter(|t| *t >= 0.0 && (org.y + dir.y * t).abs() <= half_height)
+            .map(|t| {
+                let p = org + dir * t;
+                (t, Vec3::new(p.x, 0.0, p.z).normalize())
+            })
+            .next()
+    }
+
+    /// Returns the shape's surface as triangles.
+    /// Round shapes are approximated by the convex hull of some of their points.
+    pub fn triangles(&self) -> Vec<Triangle> {
+        const SECTORS: usize = 12;
+        const STACKS: usize = 6;
+
+        if let WorldShape::ConvexHull(hull) = self {
+            return hull.triangles();
+        }
+
+        let mut directions = vec![Vec3::Y, Vec3::NEG_Y];
+        for stack in 1..STACKS {
+            let phi = std::f32::consts::PI * stack as f32 / STACKS as f32;
+            for sector in 0..SECTORS {
+                let theta = std::f32::consts::TAU * sector as f32 / SECTORS as f32;
+                directions.push(Vec3::new(
+                    phi.sin() * theta.cos(),
+                    phi.cos(),
+                    phi.sin() * theta.sin(),
+                ));
+            }
+        }
+        // so boxes get their corners
+        for x in [-1.0, 1.0] {
+            for y in [-1.0, 1.0] {
+                for z in [-1.0, 1.0] {
+                    directions.push(Vec3::new(x, y, z));
+                }
+            }
+        }
+
+        let points: Vec<Vec3> = directions.iter().map(|d| self.support(*d)).collect();
+        ConvexHull::new(&points).map_or(Vec::new(), |hull| hull.triangles())
+    }
+}
+
+/// Convex shapes, as far as GJK is concerned.
+pub trait Convex {
+    fn support(&self, dir: Vec3) -> Vec3;
+    fn center(&self) -> Vec3;
+}
+
+impl Convex for WorldShape {
+    fn support(&self, dir: Vec3) -> Vec3 {
+        WorldShape::support(self, dir)
+    }
+
+    fn center(&self) -> Vec3 {
+        WorldShape::center(self)
+    }
+}
+
+/// Every point a shape goes through when moved along `motion`
+/// (without rotating).
+pub struct Swept<'a> {
+    pub shape: &'a WorldShape,
+    pub motion: Vec3,
+}
+
+impl Convex for Swept<'_> {
+    fn support(&self, dir: Vec3) -> Vec3 {
+        let p = self.shape.support(dir);
+        if self.motion.dot(dir) > 0.0 {
+            p + self.motion
+        } else {
+            p
+        }
+    }
+
+    fn center(&self) -> Vec3 {
+        self.shape.center() + self.motion / 2.0
+    }
+}
+
+/// A collider's path from one transform to another (see `Collider::sweep`).
+///
+/// The path is split in steps that turn at most `MAX_STEP_ANGLE` radians,
+/// each one swept by translating the shape at the step's start, so a
+/// fast collider can't jump over whatever lies between both transforms.
+pub struct Sweep {
+    // (shape at the step's start, step's translation)
+    steps: Vec<(WorldShape, Vec3)>,
+}
+
+impl Sweep {
+    pub const MAX_STEP_ANGLE: f32 = 0.17; // ~10°
+    const TOI_ITERATIONS: usize = 16;
+
+    /// Checks whether the collider touches `other` anywhere along its path.
+    pub fn intersects(&self, other: &WorldShape) -> bool {
+        self.steps.iter().any(|(shape, motion)| {
+            WorldShape::gjk(
+                &Swept {
+                    shape,
+                    motion: *motion,
+                },
+                other,
+            )
+        })
+    }
+
+    /// Returns how far along its path (0 at the start, 1 at the end)
+    /// the collider first touches `other`.
+    pub fn time_of_impact(&self, other: &WorldShape) -> Option<f32> {
+        let n = self.steps.len() as f32;
+        let touches =
+            |shape: &WorldShape, motion: Vec3| WorldShape::gjk(&Swept { shape, motion }, other);
+
+        let (i, (shape, motion)) = self
+            .steps
+            .iter()
+            .enumerate()
+            .find(|(_, (shape, motion))| touches(shape, *motion))?;
+
+        // shortest part of the step that touches `other`
+        let (mut lo, mut hi) = (0.0, 1.0);
+        if shape.intersects(other) {
+            hi = 0.0;
+        }
+        for _ in 0..Self::TOI_ITERATIONS {
+            if hi == 0.0 {
+                break;
+            }
+            let mid = (lo + hi) / 2.0;
+            if touches(shape, *motion * mid) {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+
+        Some((i as f32 + hi) / n)
+    }
+}
+
+/// Convex hull of a set of points, as outward facing triangles.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConvexHull {
+    pub points: Vec<Vec3>,
+    pub faces: Vec<[usize; 3]>,
+}
+
+impl ConvexHull {
+    /// Computes the convex hull of `points` (incrementally: starting with
+    /// a tetrahedron, adding one point at a time).
+    /// Returns None when the points are (almost) flat.
+    pub fn new(points: &[Vec3]) -> Option<Self> {
+        if points.len() < 4 {
+            return None;
+        }
+
+        let size = points
+            .iter()
+            .fold(Vec3::ZERO, |acc, p| acc.max(p.abs()))
+            .max_element();
+        let eps = size.max(1.0) * 0.00001;
+
+        // initial tetrahedron, as big as possible
+        let furthest = |f: &dyn Fn(Vec3) -> f32| {
+            (0..points.len())
+                .max_by(|a, b| f(points[*a]).total_cmp(&f(points[*b])))
+                .unwrap()
+        };
+        let i0 = furthest(&|p| -p.x);
+        let i1 = furthest(&|p| p.distance(points[i0]));
+        let line = (points[i1] - points[i0]).normalize_or_zero();
+        let i2 = furthest(&|p| (p - points[i0]).cross(line).length());
+        let normal = (points[i1] - points[i0])
+            .cross(points[i2] - points[i0])
+            .normalize_or_zero();
+        let i3 = furthest(&|p| (p - points[i0]).dot(normal).abs());
+
+        if points[i0].distance(points[i1]) < eps
+            || (points[i2] - points[i0]).cross(line).length() < eps
+            || (points[i3] - points[i0]).dot(normal).abs() < eps
+        {
+            return None;
+        }
+
+        let mut hull = ConvexHull {
+            points: points.to_vec(),
+            faces: Vec::new(),
+        };
+        let tetrahedron = [i0, i1, i2, i3];
+        for (i, j, k, opposite) in [(0, 1, 2, 3), (0, 3, 1, 2), (1, 3, 2, 0), (2, 3, 0, 1)] {
+            let face = [tetrahedron[i], tetrahedron[j], tetrahedron[k]];
+            // the remaining vertex must be behind every face
+            if hull.distance(face, points[tetrahedron[opposite]]) > 0.0 {
+                hull.faces.push([face[0], face[2], face[1]]);
+            } else {
+                hull.faces.push(face);
+            }
+        }
+
+        for (i, p) in points.iter().enumerate() {
+            if tetrahedron.contains(&i) {
+                continue;
+            }
+
+            let (visible, hidden): (Vec<[usize; 3]>, Vec<[usize; 3]>) = hull
+                .faces
+                .iter()
+                .partition(|face| hull.distance(**face, *p) > eps);
+            if visible.is_empty() {
+                continue;
+            }
+
+            // edges around the visible faces
+            let edges: Vec<(usize, usize)> = visible
+                .iter()
+                .flat_map(|f| [(f[0], f[1]), (f[1], f[2]), (f[2], f[0])])
+                .collect();
+            let horizon = edges
+                .iter()
+                .filter(|(a, b)| !edges.contains(&(*b, *a)))
+                .map(|(a, b)| [*a, *b, i]);
+
+            hull.faces = hidden;
+            hull.faces.extend(horizon);
+        }
+
+        Some(hull)
+    }
+
+    /// Signed distance from `face`'s plane to `p` (positive in front).
+    fn distance(&self, face: [usize; 3], p: Vec3) -> f32 {
+        let [a, b, c] = face.map(|i| self.points[i]);
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        normal.dot(p - a)
+    }
+
+    pub fn triangles(&self) -> Vec<Triangle> {
+        self.faces
+            .iter()
+            .map(|[a, b, c]| Triangle {
+                a: self.points[*a],
+                b: self.points[*b],
+                c: self.points[*c],
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod convex_hull_tests {
+    use super::*;
+
+    fn cube_points() -> Vec<Vec3> {
+        let mut points = Vec::new();
+        for x in [-1.0, 1.0] {
+            for y in [-1.0, 1.0] {
+                for z in [-1.0, 1.0] {
+                    points.push(Vec3::new(x, y, z));
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn test_hull_ignores_inner_points() {
+        let mut points = cube_points();
+        points.push(Vec3::ZERO);
+        points.push(Vec3::new(0.5, -0.2, 0.9));
+        let hull = ConvexHull::new(&points).unwrap();
+
+        assert_eq!(hull.faces.len(), 12);
+        assert!(hull.faces.iter().flatten().all(|i| *i < 8));
+    }
+
+    #[test]
+    fn test_hull_faces_point_outwards() {
+        let mut points = cube_points();
+        points.push(Vec3::new(0.0, 3.0, 0.0));
+        let hull = ConvexHull::new(&points).unwrap();
+
+        for face in hull.faces.iter() {
+            assert!(hull.distance(*face, Vec3::ZERO) < 0.0);
+        }
+    }
+
+    #[test]
+    fn test_flat_points_have_no_hull() {
+        let points = [Vec3::ZERO, Vec3::X, Vec3::Z, Vec3::new(1.0, 0.0, 1.0)];
+
+        assert_eq!(ConvexHull::new(&points), None);
+    }
+}
+
+#[cfg(test)]
+mod collision_shape_tests {
+    use super::*;
+
+    fn at(shape: CollisionShape, transform: Transform) -> WorldShape {
+        Collider::new(shape).world(&transform.into())
+    }
+
+    fn unit_box() -> CollisionShape {
+        CollisionShape::Obb {
+            half_extents: Vec3::splat(0.5),
+        }
+    }
+
+    fn sphere(radius: f32) -> CollisionShape {
+        CollisionShape::Sphere { radius }
+    }
+
+    #[test]
+    fn test_aabb_contains_rotated_box() {
+        let shape = at(
+            CollisionShape::Aabb {
+                half_extents: Vec3::new(1.0, 0.5, 0.5),
+            },
+            Transform::from_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)),
+        );
+
+        match shape {
+            WorldShape::Aabb { max, .. } => {
+                assert!((max - Vec3::new(0.5, 0.5, 1.0)).length() < 0.0001)
+            }
+            _ => panic!("not an aabb"),
+        }
+    }
+
+    #[test]
+    fn test_spheres_intersect() {
+        let a = at(sphere(1.0), Transform::IDENTITY);
+
+        assert!(a.intersects(&at(sphere(1.0), Transform::from_xyz(1.9, 0.0, 0.0))));
+        assert!(!a.intersects(&at(sphere(1.0), Transform::from_xyz(2.1, 0.0, 0.0))));
+    }
+
+    #[test]
+    fn test_rotated_box_intersects_sphere() {
+        // turned 45°, the box' corner reaches ~0.707 along x
+        let rotated = Transform::from_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_4));
+        let a = at(unit_box(), rotated);
+
+        assert!(a.intersects(&at(sphere(0.2), Transform::from_xyz(0.85, 0.0, 0.0))));
+        assert!(!a.intersects(&at(sphere(0.2), Transform::from_xyz(0.95, 0.0, 0.0))));
+        assert!(!at(unit_box(), Transform::IDENTITY)
+            .intersects(&at(sphere(0.2), Transform::from_xyz(0.75, 0.0, 0.0))));
+    }
+
+    #[test]
+    fn test_capsule_intersects_cylinder() {
+        let capsule = at(
+            CollisionShape::Capsule {
+                radius: 0.2,
+                half_length: 1.0,
+            },
+            Transform::IDENTITY,
+        );
+        let cylinder = |x: f32, y: f32| {
+            at(
+                CollisionShape::Cylinder {
+                    radius: 0.3,
+                    half_height: 0.5,
+                },
+                Transform::from_xyz(x, y, 0.0),
+            )
+        };
+
+        assert!(capsule.intersects(&cylinder(0.45, 0.0)));
+        assert!(!capsule.intersects(&cylinder(0.55, 0.0)));
+        // past the capsule's rounded end
+        assert!(capsule.intersects(&cylinder(0.0, 1.65)));
+        assert!(!capsule.intersects(&cylinder(0.0, 1.75)));
+    }
+
+    #[test]
+    fn test_hull_intersects_box() {
+        let hull = ConvexHull::new(&[Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z]).unwrap();
+        let hull = at(CollisionShape::ConvexHull(hull), Transform::IDENTITY);
+
+        assert!(hull.intersects(&at(unit_box(), Transform::from_xyz(0.6, 0.6, 0.0))));
+        assert!(!hull.intersects(&at(unit_box(), Transform::from_xyz(1.1, 1.1, 1.1))));
+    }
+
+    #[test]
+    fn test_ray_hits_sphere_and_box() {
+        let ray = Ray::new(Vec3::ZERO, Vec3::X);
+        let hit = at(sphere(1.0), Transform::from_xyz(5.0, 0.0, 0.0))
+            .cast(&ray)
+            .unwrap();
+
+        assert!((hit.distance - 4.0).abs() < 0.0001);
+        assert!((hit.normal - Vec3::NEG_X).length() < 0.0001);
+
+        let hit = at(
+            unit_box(),
+            Transform::from_xyz(3.0, 0.0, 0.0).with_scale(Vec3::splat(2.0)),
+        )
+        .cast(&ray)
+        .unwrap();
+        assert!((hit.distance - 2.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_ray_pointing_away_misses_shapes() {
+        let ray = Ray::new(Vec3::ZERO, Vec3::NEG_X);
+        let t = Transform::from_xyz(3.0, 0.0, 0.0);
+
+        assert_eq!(at(sphere(1.0), t).cast(&ray), None);
+        assert_eq!(at(unit_box(), t).cast(&ray), None);
+        assert_eq!(
+            at(
+                CollisionShape::Capsule {
+                    radius: 0.5,
+                    half_length: 1.0
+                },
+                t
+            )
+            .cast(&ray),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ray_hits_cylinder_cap_and_side() {
+        let cylinder = at(
+            CollisionShape::Cylinder {
+                radius: 0.5,
+                half_height: 1.0,
+            },
+            Transform::IDENTITY,
+        );
+
+        let from_above = Ray::new(Vec3::new(0.2, 5.0, 0.0), Vec3::NEG_Y);
+        let hit = cylinder.cast(&from_above).unwrap();
+        assert!((hit.distance - 4.0).abs() < 0.0001);
+        assert_eq!(hit.normal, Vec3::Y);
+
+        let from_side = Ray::new(Vec3::new(-3.0, 0.5, 0.0), Vec3::X);
+        let hit = cylinder.cast(&from_side).unwrap();
+        assert!((hit.distance - 2.5).abs() < 0.0001);
+
+        assert_eq!(cylinder.cast(&from_side.with_max_distance(2.0)), None);
+    }
+
+    #[test]
+    fn test_ray_hits_capsule_end() {
+        let capsule = at(
+            CollisionShape::Capsule {
+                radius: 0.5,
+                half_length: 1.0,
+            },
+            Transform::IDENTITY,
+        );
+        let hit = capsule
+            .cast(&Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::NEG_Y))
+            .unwrap();
+
+        assert!((hit.distance - 3.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_ray_from_inside_hits_on_the_way_out() {
+        let hit = at(unit_box(), Transform::IDENTITY)
+            .cast(&Ray::new(Vec3::ZERO, Vec3::Z))
+            .unwrap();
+
+        assert!((hit.distance - 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_fast_sphere_doesnt_tunnel() {
+        let sphere = Collider::new(sphere(0.1));
+        let wall = at(
+            CollisionShape::Obb {
+                half_extents: Vec3::new(0.05, 1.0, 1.0),
+            },
+            Transform::IDENTITY,
+        );
+        let from = Transform::from_xyz(-5.0, 0.0, 0.0);
+        let to = Transform::from_xyz(5.0, 0.0, 0.0);
+
+        // neither end touches the wall
+        assert!(!sphere.world(&from.into()).intersects(&wall));
+        assert!(!sphere.world(&to.into()).intersects(&wall));
+
+        let sweep = sphere.sweep(&from.into(), &to.into());
+        assert!(sweep.intersects(&wall));
+        let toi = sweep.time_of_impact(&wall).unwrap();
+        assert!((toi - 0.485).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_sweep_misses_shapes_off_the_path() {
+        let sweep = Collider::new(sphere(0.1)).sweep(
+            &Transform::from_xyz(-5.0, 0.0, 0.0).into(),
+            &Transform::from_xyz(5.0, 0.0, 0.0).into(),
+        );
+        let off_path = at(sphere(0.5), Transform::from_xyz(0.0, 0.0, 1.0));
+
+        assert!(!sweep.intersects(&off_path));
+        assert_eq!(sweep.time_of_impact(&off_path), None);
+    }
+
+    #[test]
+    fn test_turning_sweep_follows_the_arc() {
+        // a long bar turning (almost) half a circle sweeps a disk, not a line
+        let bar = Collider::new(CollisionShape::Obb {
+            half_extents: Vec3::new(1.0, 0.1, 0.1),
+        })
+        .with_offset(Transform::from_xyz(1.0, 0.0, 0.0));
+        let sweep = bar.sweep(
+            &Transform::IDENTITY.into(),
+            &Transform::from_rotation(Quat::from_rotation_y(2.8)).into(),
+        );
+
+        // turning around y takes x to -z
+        assert!(sweep.intersects(&WorldShape::point(Vec3::new(0.0, 0.0, -1.5))));
+        assert!(!sweep.intersects(&WorldShape::point(Vec3::new(0.0, 0.0, 1.5))));
+    }
+
+    #[test]
+    fn test_triangles_enclose_shape() {
+        let triangles = at(sphere(1.0), Transform::from_xyz(2.0, 0.0, 0.0)).triangles();
+        let hit = Ray::new(Vec3::ZERO, Vec3::X).cast(&triangles).unwrap();
+
+        assert!(!triangles.is_empty());
+        assert!((hit.distance - 1.0).abs() < 0.05);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{
+    collision::{CollisionLayers, DistanceCollision, Obstacle, ObstacleProxy},
+    collision_shape::{Collider, CollisionShape, HasCollider},
+};
+use bevy::prelude::*;
+
+/// Stuff found on the beach that isn't a can.
+///
+/// Debris is there to fool Arenito's vision: most of it shows up as
+/// dark blobs on the sand. Arenito mustn't collect any of it, picking
+/// up debris counts as a distractor pickup (see `EpisodeStats`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DebrisKind {
+    Bottle,
+    BottleCap,
+    Shell,
+    Seaweed,
+    DarkRock,
+    Footprint,
+    Towel,
+}
+
+impl DebrisKind {
+    /// Whether the brush can pull it in. Footprints are holes in the sand,
+    /// and towels are too big (the brush just rolls over them).
+    pub fn pickable(&self) -> bool {
+        !matches!(self, DebrisKind::Footprint | DebrisKind::Towel)
+    }
+
+    /// Probability of the brush pulling it in, once it's in the capture zone.
+    pub fn pickup_probability(&self) -> f32 {
+        match self {
+            DebrisKind::Bottle => 0.6,
+            DebrisKind::BottleCap => 0.9,
+            DebrisKind::Shell => 0.8,
+            DebrisKind::Seaweed => 0.5,
+            DebrisKind::DarkRock => 0.3,
+            DebrisKind::Footprint | DebrisKind::Towel => 0.0,
+        }
+    }
+
+    fn mesh(&self) -> Mesh {
+        match self {
+            DebrisKind::Bottle => shape::Cylinder {
+                radius: 0.1,
+                height: 0.5,
+                resolution: 12,
+                segments: 1,
+            }
+            .into(),
+            DebrisKind::BottleCap => shape::Cylinder {
+                radius: 0.05,
+                height: 0.03,
+                resolution: 10,
+                segments: 1,
+            }
+            .into(),
+            DebrisKind::Shell => shape::UVSphere {
+                radius: 0.08,
+                sectors: 10,
+                stacks: 6,
+            }
+            .into(),
+            DebrisKind::Seaweed => shape::Box::new(0.6, 0.02, 0.25).into(),
+            DebrisKind::DarkRock => shape::Icosphere {
+                radius: 0.2,
+                subdivisions: 1,
+            }
+            .try_into()
+            .unwrap(),
+            DebrisKind::Footprint => shape::Box::new(0.28, 0.005, 0.12).into(),
+            DebrisKind::Towel => shape::Box::new(1.8, 0.01, 1.0).into(),
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            DebrisKind::Bottle => Color::rgb(0.1, 0.3, 0.15),
+            DebrisKind::BottleCap => Color::rgb(0.7, 0.1, 0.1),
+            DebrisKind::Shell => Color::rgb(0.9, 0.85, 0.75),
+            DebrisKind::Seaweed => Color::rgb(0.15, 0.2, 0.05),
+            DebrisKind::DarkRock => Color::rgb(0.12, 0.12, 0.12),
+            DebrisKind::Footprint => Color::rgb(0.55, 0.45, 0.3),
+            DebrisKind::Towel => Color::rgb(0.2, 0.3, 0.8),
+        }
+    }
+
+    /// How it's laid on the sand: (height over the ground, rotation around z, scale).
+    fn resting_pose(&self) -> (f32, f32, Vec3) {
+        match self {
+            DebrisKind::Bottle => (0.1, std::f32::consts::FRAC_PI_2, Vec3::ONE),
+            DebrisKind::BottleCap => (0.015, 0.0, Vec3::ONE),
+            DebrisKind::Shell => (0.02, 0.0, Vec3::new(1.0, 0.4, 1.0)),
+            DebrisKind::Seaweed => (0.01, 0.0, Vec3::ONE),
+            DebrisKind::DarkRock => (0.1, 0.0, Vec3::new(1.0, 0.7, 1.2)),
+            DebrisKind::Footprint => (0.0, 0.0, Vec3::ONE),
+            DebrisKind::Towel => (0.005, 0.0, Vec3::ONE),
+        }
+    }
+}
+
+/// Debris' position in a scene: (kind, x, z, rotation around y).
+pub type DebrisData = (DebrisKind, f32, f32, f32);
+
+#[derive(Component, Clone, Copy)]
+pub struct Debris {
+    pub kind: DebrisKind,
+}
+
+impl Debris {
+    /// Returns the debris' transform when placed at (x, z), rotated `ry`
+    /// radians around the y axis, given the ground's height there.
+    pub fn placement(&self, x: f32, z: f32, ry: f32, ground: f32) -> Transform {
+        let (lift, rz, scale) = self.kind.resting_pose();
+        Transform::from_xyz(x, ground + lift, z)
+            .with_rotation(Quat::from_euler(EulerRot::XYZ, 0.0, ry, rz))
+            .with_scale(scale)
+    }
+
+    /// Spawns the debris, returns its entity.
+    pub fn spawn(
+        self,
+        commands: &mut Commands,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<StandardMaterial>,
+        transform: Transform,
+    ) -> Entity {
+        commands
+            .spawn((
+                PbrBundle {
+                    mesh: meshes.add(self.kind.mesh()),
+                    material: materials.add(StandardMaterial {
+                        base_color: self.kind.color(),
+                        perceptual_roughness: 0.8,
+                        ..default()
+                    }),
+                    transform,
+                    ..default()
+                },
+                self,
+                Obstacle::empty()
+                    .with_proxy(ObstacleProxy::ConvexHull)
+                    .with_layers(CollisionLayers::DEBRIS),
+            ))
+            .id()
+    }
+}
+
+impl DistanceCollision for Debris {
+    fn get_radius(&self) -> f32 {
+        match self.kind {
+            DebrisKind::Bottle => 0.27,
+            DebrisKind::BottleCap => 0.05,
+            DebrisKind::Shell => 0.08,
+            DebrisKind::Seaweed => 0.32,
+            DebrisKind::DarkRock => 0.24,
+            DebrisKind::Footprint => 0.15,
+            DebrisKind::Towel => 1.03,
+        }
+    }
+}
+
+impl HasCollider for Debris {
+    /// Simple shape around its mesh (before the resting pose's scale).
+    fn collider(&self) -> Collider {
+        Collider::new(match self.kind {
+            DebrisKind::Bottle => CollisionShape::Cylinder {
+                radius: 0.1,
+                half_height: 0.25,
+            },
+            DebrisKind::BottleCap => CollisionShape::Cylinder {
+                radius: 0.05,
+                half_height: 0.015,
+            },
+            DebrisKind::Shell => CollisionShape::Sphere { radius: 0.08 },
+            DebrisKind::Seaweed => CollisionShape::Obb {
+                half_extents: Vec3::new(0.3, 0.01, 0.125),
+            },
+            DebrisKind::DarkRock => CollisionShape::Sphere { radius: 0.2 },
+            DebrisKind::Footprint => CollisionShape::Obb {
+                half_extents: Vec3::new(0.14, 0.0025, 0.06),
+            },
+            DebrisKind::Towel => CollisionShape::Obb {
+                half_extents: Vec3::new(0.9, 0.005, 0.5),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod debris_tests {
+    use super::*;
+
+    #[test]
+    fn test_footprints_and_towels_cant_be_picked_up() {
+        assert!(!DebrisKind::Footprint.pickable());
+        assert!(!DebrisKind::Towel.pickable());
+        assert!(DebrisKind::BottleCap.pickable());
+    }
+
+    #[test]
+    fn test_placement_rests_on_ground() {
+        let footprint = Debris {
+            kind: DebrisKind::Footprint,
+        };
+        let bottle = Debris {
+            kind: DebrisKind::Bottle,
+        };
+
+        assert_eq!(footprint.placement(1.0, 2.0, 0.0, 0.5).translation.y, 0.5);
+        // bottles lie on their side
+        let axis = bottle
+            .placement(1.0, 2.0, 0.0, 0.0)
+            .rotation
+            .mul_vec3(Vec3::Y);
+        assert!(axis.y.abs() < 0.001);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{
+    cans::{CanId, CanManager},
+    stats::EpisodeStats,
+};
+use bevy::prelude::*;
+
+/// The deposit Arenito has to dump the cans into.
+///
+/// Cans are considered deposited when they land inside the deposit's
+/// collection volume: a box centered at `CENTER` (relative to the
+/// deposit) with size `2 * HALF_EXTENTS`.
+#[derive(Component)]
+pub struct Deposit;
+
+impl Deposit {
+    const CENTER: Vec3 = Vec3::new(0.0, 0.3, 0.0);
+    const HALF_EXTENTS: Vec3 = Vec3::new(1.4, 0.4, 1.4);
+
+    /// Checks whether `point` is inside the deposit's collection volume.
+    pub fn contains(&self, transform: &Transform, point: Vec3) -> bool {
+        let local = transform.compute_matrix().inverse().transform_point3(point) - Self::CENTER;
+        local.abs().cmple(Self::HALF_EXTENTS).all()
+    }
+
+    /// Draws the collection volume.
+    pub fn draw_volume(&self, transform: &Transform, gizmos: &mut Gizmos) {
+        gizmos.cuboid(
+            Transform::from_translation(transform.transform_point(Self::CENTER))
+                .with_rotation(transform.rotation)
+                .with_scale(Self::HALF_EXTENTS * 2.0),
+            Color::WHITE,
+        );
+    }
+}
+
+/// Marks a can that has just been dumped by Arenito, and hasn't
+/// been scored yet.
+#[derive(Component)]
+pub struct DumpedCan;
+
+/// Marks a can that landed inside the deposit.
+#[derive(Component)]
+pub struct Deposited;
+
+/// Checks where freshly dumped cans landed.
+/// Cans inside the deposit are counted as deposited, the rest stay
+/// on the sand as litter.
+pub fn score_dumped_cans(
+    mut commands: Commands,
+    mut stats: ResMut<EpisodeStats>,
+    mut can_manager: ResMut<CanManager>,
+    deposits: Query<(&Deposit, &Transform)>,
+    cans: Query<(Entity, &CanId, &Transform), With<DumpedCan>>,
+) {
+    for (ent, id, can_transform) in cans.iter() {
+        let mut can = commands.entity(ent);
+        can.remove::<DumpedCan>();
+
+        if deposits
+            .iter()
+            .any(|(deposit, t)| deposit.contains(t, can_transform.translation))
+        {
+            can.insert(Deposited);
+            can_manager.deposit(*id);
+            stats.deposited_cans += 1;
+        } else {
+            stats.littered_cans += 1;
+        }
+    }
+}
+
+pub fn draw_deposit_volume(mut gizmos: Gizmos, deposits: Query<(&Deposit, &Transform)>) {
+    for (deposit, transform) in deposits.iter() {
+        deposit.draw_volume(transform, &mut gizmos);
+    }
+}
+
+#[cfg(test)]
+mod deposit_tests {
+    use super::*;
+
+    #[test]
+    fn test_deposit_contains_point_inside() {
+        let transform = Transform::from_xyz(3.0, 0.0, -3.0);
+
+        assert!(Deposit.contains(&transform, Vec3::new(3.5, 0.2, -2.0)));
+    }
+
+    #[test]
+    fn test_deposit_doesnt_contain_point_outside() {
+        let transform = Transform::from_xyz(3.0, 0.0, -3.0);
+
+        assert!(!Deposit.contains(&transform, Vec3::new(1.0, 0.2, -3.0)));
+        assert!(!Deposit.contains(&transform, Vec3::new(3.0, 2.0, -3.0)));
+    }
+
+    #[test]
+    fn test_rotated_deposit_contains_point() {
+        let transform = Transform::from_xyz(0.0, 0.0, 0.0)
+            .with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_4));
+
+        // the corner of the unrotated box is out of the rotated box
+        assert!(!Deposit.contains(&transform, Vec3::new(1.35, 0.2, 1.35)));
+        assert!(Deposit.contains(&transform, Vec3::new(1.8, 0.2, 0.0)));
+    }
+}
@@ -0,0 +1,565 @@
+use crate::noise::{NoiseModel, SensorNoise};
+use bevy::prelude::*;
+use image::{codecs::jpeg::JpegEncoder, Rgb, RgbImage};
+use rand::Rng;
+
+/// Brown-Conrady lens distortion coefficients (OpenCV's order), as
+/// found by calibrating the real camera.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LensDistortion {
+    pub k1: f32,
+    pub k2: f32,
+    pub p1: f32,
+    pub p2: f32,
+    pub k3: f32,
+}
+
+impl LensDistortion {
+    /// Takes an undistorted point (normalized image coordinates) to
+    /// where the lens puts it.
+    pub fn distort(&self, p: Vec2) -> Vec2 {
+        let r2 = p.length_squared();
+        let radial = 1.0 + self.k1 * r2 + self.k2 * r2 * r2 + self.k3 * r2 * r2 * r2;
+        let tangential = Vec2::new(
+            2.0 * self.p1 * p.x * p.y + self.p2 * (r2 + 2.0 * p.x * p.x),
+            self.p1 * (r2 + 2.0 * p.y * p.y) + 2.0 * self.p2 * p.x * p.y,
+        );
+        p * radial + tangential
+    }
+
+    /// Finds the undistorted point the lens took to `p` (iteratively,
+    /// like OpenCV's `undistortPoints`).
+    pub fn undistort(&self, p: Vec2) -> Vec2 {
+        let mut u = p;
+        for _ in 0..8 {
+            u -= self.distort(u) - p;
+        }
+        u
+    }
+}
+
+/// A camera's exposure, gain and white balance, as set by the AI.
+///
+/// Renders look right at `NOMINAL_EXPOSURE` with no gain: longer exposures
+/// (or more gain) brighten the frame, until it clips to white.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraSettings {
+    /// Shutter time in seconds, None for auto exposure.
+    pub exposure: Option<f32>,
+    pub gain: f32,
+    /// Per channel (r, g, b) gains.
+    pub white_balance: Vec3,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            exposure: Some(Self::NOMINAL_EXPOSURE),
+            gain: 1.0,
+            white_balance: Vec3::ONE,
+        }
+    }
+}
+
+impl CameraSettings {
+    pub const NOMINAL_EXPOSURE: f32 = 1.0 / 60.0;
+    // auto exposure's range
+    pub const MIN_EXPOSURE: f32 = 0.0001;
+    pub const MAX_EXPOSURE: f32 = 0.1;
+    // auto exposure aims for this mean (linear) brightness
+    const AUTO_EXPOSURE_TARGET: f32 = 0.18;
+
+    /// Exposes `img` (a render at nominal settings), with `glare` (see
+    /// `Capture::glare`) shining into the lens.
+    /// Returns the exposed frame and the exposure used.
+    pub fn expose(&self, img: RgbImage, glare: f32) -> (RgbImage, f32) {
+        let to_linear: Vec<f32> = (0..=255u8)
+            .map(|v| srgb_to_linear(v as f32 / 255.0))
+            .collect();
+        let exposure = match self.exposure {
+            Some(exposure) => exposure,
+            None => {
+                let mean = img.iter().map(|v| to_linear[*v as usize]).sum::<f32>()
+                    / img.len().max(1) as f32;
+                let lit = (mean + glare) * self.gain;
+                let exposure = Self::NOMINAL_EXPOSURE * Self::AUTO_EXPOSURE_TARGET / lit.max(0.001);
+                exposure.clamp(Self::MIN_EXPOSURE, Self::MAX_EXPOSURE)
+            }
+        };
+
+        let scale = exposure / Self::NOMINAL_EXPOSURE * self.gain;
+        let gains = (self.white_balance * scale).to_array();
+        if gains == [1.0; 3] && glare <= 0.0 {
+            return (img, exposure);
+        }
+
+        let img = map_pixels(img, |_, _, px| {
+            Rgb([0, 1, 2].map(|c| {
+                // whatever goes past white clips
+                let v = (to_linear[px[c] as usize] * gains[c] + glare * scale).min(1.0);
+                (linear_to_srgb(v) * 255.0).round() as u8
+            }))
+        });
+        (img, exposure)
+    }
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// What the camera was doing when the frame was taken.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Capture {
+    /// Focal length, in pixels of the exported frame.
+    pub focal: f32,
+    /// Speed along the camera's view (negative when backing away).
+    pub forward_speed: f32,
+    /// Turning speed (rad/s), positive to the left.
+    pub yaw_rate: f32,
+    pub settings: CameraSettings,
+    /// Sunlight shining straight into the lens (linear brightness,
+    /// at nominal exposure), see `Capture::glare`.
+    pub glare: f32,
+}
+
+impl Capture {
+    // how fast glare fades as the sun leaves the camera's view
+    const GLARE_FALLOFF: i32 = 16;
+    // illuminance that washes the frame out at nominal exposure
+    const GLARE_ILLUMINANCE: f32 = 10_000.0;
+
+    /// Returns how much glare a camera looking along `view` gets from
+    /// `lights`: (illuminance, direction towards the light).
+    pub fn glare(view: Vec3, lights: impl IntoIterator<Item = (f32, Vec3)>) -> f32 {
+        lights
+            .into_iter()
+            .map(|(illuminance, towards)| {
+                let facing = view.normalize().dot(towards.normalize()).max(0.0);
+                illuminance / Self::GLARE_ILLUMINANCE * facing.powi(Self::GLARE_FALLOFF)
+            })
+            .sum()
+    }
+}
+
+/// One step of a frame's degradation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameEffect {
+    /// Per pixel noise, on each channel (0..255).
+    Noise(SensorNoise),
+    /// Compression artifacts.
+    Jpeg { quality: u8 },
+    /// Per channel (r, g, b) gains.
+    WhiteBalance(Vec3),
+    /// Darkens the frame towards its corners (`strength` at the corners).
+    Vignetting { strength: f32 },
+    /// Barrel (or pincushion) distortion.
+    Distortion(LensDistortion),
+    /// Blur from the camera moving while the shutter is open. `depth` is
+    /// the distance to what's in view, the closer it is the more moving
+    /// forward smears it.
+    MotionBlur { depth: f32 },
+}
+
+/// Post-processing applied to exported frames, in order, so that they
+/// look like the real camera's.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FramePipeline {
+    pub effects: Vec<FrameEffect>,
+}
+
+impl FramePipeline {
+    // samples along the camera's motion
+    const MOTION_BLUR_SAMPLES: usize = 8;
+
+    /// Clean renders.
+    pub fn clean() -> Self {
+        FramePipeline::default()
+    }
+
+    /// Looks like the Jetson's CSI camera (IMX219, wide angle lens).
+    pub fn jetson_csi() -> Self {
+        FramePipeline::clean()
+            .with(FrameEffect::MotionBlur { depth: 2.0 })
+            .with(FrameEffect::Distortion(LensDistortion {
+                k1: -0.28,
+                k2: 0.07,
+                p1: 0.0005,
+                p2: -0.0003,
+                k3: 0.0,
+            }))
+            .with(FrameEffect::Vignetting { strength: 0.35 })
+            .with(FrameEffect::WhiteBalance(Vec3::new(1.06, 1.0, 0.9)))
+            .with(FrameEffect::Noise(SensorNoise::new(vec![
+                NoiseModel::Gaussian { std_dev: 4.0 },
+                NoiseModel::Quantization(1.0),
+            ])))
+            .with(FrameEffect::Jpeg { quality: 75 })
+    }
+
+    pub fn with(mut self, effect: FrameEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Exposes `img` with the camera's settings, then degrades it,
+    /// taken as `capture` says.
+    pub fn apply(&mut self, img: RgbImage, capture: &Capture, rng: &mut impl Rng) -> RgbImage {
+        let (mut img, exposure) = capture.settings.expose(img, capture.glare);
+        for effect in self.effects.iter_mut() {
+            img = match effect {
+                FrameEffect::Noise(noise) => {
+                    let mut img = img;
+                    for (i, value) in img.iter_mut().enumerate() {
+                        // lost reads are dead pixels
+                        let read = noise
+                            .apply_channel(*value as f32, i % 3, rng)
+                            .unwrap_or(0.0);
+                        *value = read.clamp(0.0, 255.0) as u8;
+                    }
+                    img
+                }
+                FrameEffect::Jpeg { quality } => jpeg(&img, *quality),
+                FrameEffect::WhiteBalance(gains) => {
+                    let gains = gains.to_array();
+                    map_pixels(img, |_, _, px| {
+                        Rgb([0, 1, 2].map(|c| (px[c] as f32 * gains[c]).clamp(0.0, 255.0) as u8))
+                    })
+                }
+                FrameEffect::Vignetting { strength } => {
+                    let (w, h) = img.dimensions();
+                    let center = Vec2::new(w as f32, h as f32) / 2.0;
+                    let corner = center.length_squared();
+                    map_pixels(img, |x, y, px| {
+                        let r2 = (Vec2::new(x as f32, y as f32) - center).length_squared() / corner;
+                        let gain = 1.0 - *strength * r2;
+                        Rgb(px.0.map(|v| (v as f32 * gain).clamp(0.0, 255.0) as u8))
+                    })
+                }
+                FrameEffect::Distortion(lens) => {
+                    let (w, h) = img.dimensions();
+                    let center = Vec2::new(w as f32, h as f32) / 2.0;
+                    let mut out = RgbImage::new(w, h);
+                    for (x, y, px) in out.enumerate_pixels_mut() {
+                        let p = (Vec2::new(x as f32, y as f32) - center) / capture.focal;
+                        let src = lens.undistort(p) * capture.focal + center;
+                        *px = sample(&img, src).unwrap_or(Rgb([0, 0, 0]));
+                    }
+                    out
+                }
+                FrameEffect::MotionBlur { depth } => motion_blur(img, capture, exposure, *depth),
+            };
+        }
+
+        img
+    }
+}
+
+/// Returns a copy of `img` with every pixel mapped by `f(x, y, pixel)`.
+fn map_pixels(img: RgbImage, f: impl Fn(u32, u32, &Rgb<u8>) -> Rgb<u8>) -> RgbImage {
+    let mut img = img;
+    for (x, y, px) in img.enumerate_pixels_mut() {
+        *px = f(x, y, px);
+    }
+    img
+}
+
+/// Bilinear sample at `p` (pixels), None if it's outside the image.
+fn sample(img: &RgbImage, p: Vec2) -> Option<Rgb<u8>> {
+    let (w, h) = img.dimensions();
+    if p.x < 0.0 || p.y < 0.0 || p.x > (w - 1) as f32 || p.y > (h - 1) as f32 {
+        return None;
+    }
+
+    let (x0, y0) = (p.x.floor() as u32, p.y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+    let (tx, ty) = (p.x.fract(), p.y.fract());
+    let lerp = |a: &Rgb<u8>, b: &Rgb<u8>, t: f32| -> Vec3 {
+        Vec3::new(a[0] as f32, a[1] as f32, a[2] as f32) * (1.0 - t)
+            + Vec3::new(b[0] as f32, b[1] as f32, b[2] as f32) * t
+    };
+    let top = lerp(img.get_pixel(x0, y0), img.get_pixel(x1, y0), tx);
+    let bottom = lerp(img.get_pixel(x0, y1), img.get_pixel(x1, y1), tx);
+    let v = top * (1.0 - ty) + bottom * ty;
+
+    Some(Rgb([
+        v.x.round() as u8,
+        v.y.round() as u8,
+        v.z.round() as u8,
+    ]))
+}
+
+fn jpeg(img: &RgbImage, quality: u8) -> RgbImage {
+    let mut buf = Vec::new();
+    let encoded = JpegEncoder::new_with_quality(&mut buf, quality.clamp(1, 100)).encode_image(img);
+
+    match encoded.and_then(|_| image::load_from_memory(&buf)) {
+        Ok(decoded) => decoded.to_rgb8(),
+        Err(_) => {
+            println!("Cannot compress frame!");
+            img.clone()
+        }
+    }
+}
+
+/// Averages the frame over the camera's motion while the shutter is
+/// open: turning smears it sideways, moving forward smears it out from
+/// the center.
+fn motion_blur(img: RgbImage, capture: &Capture, exposure: f32, depth: f32) -> RgbImage {
+    let shift = capture.yaw_rate * exposure * capture.focal;
+    let zoom = capture.forward_speed * exposure / depth;
+    if shift.abs() < 0.5 && zoom.abs() * img.width() as f32 / 2.0 < 0.5 {
+        // less than half a pixel
+        return img;
+    }
+
+    let (w, h) = img.dimensions();
+    let center = Vec2::new(w as f32, h as f32) / 2.0;
+    let mut out = RgbImage::new(w, h);
+    for (x, y, px) in out.enumerate_pixels_mut() {
+        let p = Vec2::new(x as f32, y as f32);
+        let (sum, count) = (0..FramePipeline::MOTION_BLUR_SAMPLES)
+            .map(|i| i as f32 / (FramePipeline::MOTION_BLUR_SAMPLES - 1) as f32)
+            .filter_map(|t| {
+                // where this pixel was, t of the way through the exposure
+                let src = center + (p - center) / (1.0 + zoom * t) - Vec2::new(shift * t, 0.0);
+                sample(&img, src)
+            })
+            .fold((Vec3::ZERO, 0), |(sum, count), s| {
+                (
+                    sum + Vec3::new(s[0] as f32, s[1] as f32, s[2] as f32),
+                    count + 1,
+                )
+            });
+
+        *px = if count > 0 {
+            let v = sum / count as f32;
+            Rgb([v.x.round() as u8, v.y.round() as u8, v.z.round() as u8])
+        } else {
+            *img.get_pixel(x, y)
+        };
+    }
+    out
+}
+
+#[cfg(test)]
+mod frame_pipeline_tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    // left half black, right half white
+    fn edge() -> RgbImage {
+        RgbImage::from_fn(64, 64, |x, _| {
+            if x < 32 {
+                Rgb([0, 0, 0])
+            } else {
+                Rgb([255, 255, 255])
+            }
+        })
+    }
+
+    fn gray() -> RgbImage {
+        RgbImage::from_pixel(64, 64, Rgb([128, 128, 128]))
+    }
+
+    fn still() -> Capture {
+        Capture {
+            focal: 32.0,
+            ..default()
+        }
+    }
+
+    fn apply(effect: FrameEffect, img: RgbImage, capture: &Capture) -> RgbImage {
+        let mut rng = StdRng::seed_from_u64(0);
+        FramePipeline::clean()
+            .with(effect)
+            .apply(img, capture, &mut rng)
+    }
+
+    #[test]
+    fn test_clean_pipeline_keeps_frame() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            FramePipeline::clean().apply(edge(), &still(), &mut rng),
+            edge()
+        );
+    }
+
+    #[test]
+    fn test_white_balance_and_vignetting() {
+        let img = apply(
+            FrameEffect::WhiteBalance(Vec3::new(1.5, 1.0, 0.5)),
+            gray(),
+            &still(),
+        );
+        assert_eq!(*img.get_pixel(10, 10), Rgb([192, 128, 64]));
+
+        let img = apply(FrameEffect::Vignetting { strength: 0.5 }, gray(), &still());
+        assert_eq!(*img.get_pixel(32, 32), Rgb([128, 128, 128]));
+        assert!(img.get_pixel(0, 0)[0] < 70);
+    }
+
+    #[test]
+    fn test_noise_changes_pixels_around_their_value() {
+        let img = apply(
+            FrameEffect::Noise(SensorNoise::new(vec![NoiseModel::Gaussian {
+                std_dev: 5.0,
+            }])),
+            gray(),
+            &still(),
+        );
+
+        assert!(img.pixels().any(|px| px[0] != 128));
+        let mean = img.iter().map(|v| *v as f32).sum::<f32>() / img.len() as f32;
+        assert!((mean - 128.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_lens_undistorts_what_it_distorts() {
+        let lens = LensDistortion {
+            k1: -0.28,
+            k2: 0.07,
+            p1: 0.0005,
+            p2: -0.0003,
+            k3: 0.0,
+        };
+        let p = Vec2::new(0.6, -0.4);
+
+        assert!(lens.undistort(lens.distort(p)).distance(p) < 0.0001);
+        // barrel distortion pulls points towards the center
+        assert!(lens.distort(p).length() < p.length());
+    }
+
+    #[test]
+    fn test_barrel_distortion_keeps_the_center() {
+        let img = apply(
+            FrameEffect::Distortion(LensDistortion {
+                k1: -0.3,
+                k2: 0.0,
+                p1: 0.0,
+                p2: 0.0,
+                k3: 0.0,
+            }),
+            edge(),
+            &still(),
+        );
+
+        assert_eq!(*img.get_pixel(20, 32), Rgb([0, 0, 0]));
+        assert_eq!(*img.get_pixel(44, 32), Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn test_turning_blurs_vertical_edges() {
+        let blur = FrameEffect::MotionBlur { depth: 2.0 };
+        let turning = Capture {
+            yaw_rate: 2.0,
+            settings: CameraSettings {
+                exposure: Some(0.1),
+                ..default()
+            },
+            ..still()
+        };
+
+        assert_eq!(apply(blur.clone(), edge(), &still()), edge());
+        // (edge() is all black or white, exposure doesn't change it)
+        let img = apply(blur, edge(), &turning);
+        // the dark half gets dragged right
+        let px = img.get_pixel(34, 32)[0];
+        assert!(px > 0 && px < 255);
+    }
+
+    #[test]
+    fn test_nominal_settings_keep_every_value() {
+        let img = RgbImage::from_fn(256, 1, |x, _| Rgb([x as u8, x as u8, 255 - x as u8]));
+        let (exposed, exposure) = CameraSettings::default().expose(img.clone(), 0.0);
+
+        assert_eq!(exposed, img);
+        assert_eq!(exposure, CameraSettings::NOMINAL_EXPOSURE);
+        // even when it has to do the math
+        let settings = CameraSettings {
+            gain: 1.0 + f32::EPSILON,
+            ..default()
+        };
+        assert_eq!(settings.expose(img.clone(), 0.0).0, img);
+    }
+
+    #[test]
+    fn test_long_exposures_and_gain_brighten_until_clipping() {
+        let longer = CameraSettings {
+            exposure: Some(CameraSettings::NOMINAL_EXPOSURE * 2.0),
+            ..default()
+        };
+        let gain = CameraSettings {
+            gain: 8.0,
+            ..default()
+        };
+
+        let px = longer.expose(gray(), 0.0).0.get_pixel(0, 0)[0];
+        assert!(px > 160 && px < 255);
+        assert_eq!(
+            *gain.expose(gray(), 0.0).0.get_pixel(0, 0),
+            Rgb([255, 255, 255])
+        );
+    }
+
+    #[test]
+    fn test_white_balance_shifts_colors() {
+        let warm = CameraSettings {
+            white_balance: Vec3::new(1.3, 1.0, 0.7),
+            ..default()
+        };
+        let px = *warm.expose(gray(), 0.0).0.get_pixel(0, 0);
+
+        assert!(px[0] > 128 && px[1] == 128 && px[2] < 128);
+    }
+
+    #[test]
+    fn test_auto_exposure_compensates_glare() {
+        let auto = CameraSettings {
+            exposure: None,
+            ..default()
+        };
+        let (_, dark) = auto.expose(RgbImage::from_pixel(4, 4, Rgb([40, 40, 40])), 0.0);
+        let (_, bright) = auto.expose(gray(), 0.0);
+        let (glared, glaring) = auto.expose(gray(), 1.0);
+
+        assert!(dark > bright && bright > glaring);
+        assert!(glared.get_pixel(0, 0)[0] < 255);
+    }
+
+    #[test]
+    fn test_sun_in_view_overexposes() {
+        let view = Vec3::X;
+        let sun = Capture::glare(view, [(10_000.0, Vec3::new(1.0, 0.1, 0.0))]);
+        let behind = Capture::glare(view, [(10_000.0, Vec3::new(-1.0, 0.3, 0.0))]);
+
+        assert!(sun > 0.5);
+        assert_eq!(behind, 0.0);
+        let (img, _) = CameraSettings::default().expose(gray(), sun);
+        assert_eq!(*img.get_pixel(0, 0), Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn test_jpeg_artifacts_stay_close() {
+        let img = apply(FrameEffect::Jpeg { quality: 50 }, gray(), &still());
+
+        assert_eq!(img.dimensions(), (64, 64));
+        assert!(img.iter().all(|v| (*v as i32 - 128).abs() < 4));
+    }
+}
@@ -1,9 +1,20 @@
 pub mod arenito;
+pub mod backdoor;
+pub mod brush;
+pub mod camera_target;
 pub mod cans;
 pub mod collision;
+pub mod collision_shape;
+pub mod debris;
+pub mod deposit;
+pub mod frame;
+pub mod mount;
+pub mod noise;
 pub mod scenes;
 pub mod sensor;
 pub mod static_shape;
+pub mod stats;
+pub mod terrain;
 
 use arenito::{ArenitoConfig, ArenitoPlugin};
 use bevy::{
@@ -12,9 +23,11 @@ use bevy::{
     winit::WinitSettings,
 };
 use clap::Parser;
+use frame::FramePipeline;
 use memmap;
 use scenes::{SceneData, SceneLoaderPlugin};
-use sensor::AISimMem;
+use sensor::{AISimMem, SensorReport};
+use stats::EpisodeStatsPlugin;
 use std::{fs::OpenOptions, io::Write};
 
 /// CLI arguments
@@ -24,9 +37,18 @@ struct Args {
     /// If set, main window's default size is small
     #[arg(short, long, default_value_t = false)]
     small_window: bool,
-    /// Make Arenito's cameras windows visible
+    /// Open a window previewing each of Arenito's cameras
     #[arg(short = 'v', long, default_value_t = false)]
     visible_cameras: bool,
+    /// Send every proximity sensor's distance (in cm), instead of what the firmware sends
+    #[arg(long, default_value_t = false)]
+    raw_sensor_distances: bool,
+    /// Turn off every sensor's noise
+    #[arg(long, default_value_t = false)]
+    ideal_sensors: bool,
+    /// Make camera frames look like the real camera's (noise, lens distortion, blur...)
+    #[arg(long, default_value_t = false)]
+    degraded_frames: bool,
 }
 
 const SMALL_WINDOW_SIZE_WIDTH: f32 = 600.0;
@@ -51,6 +73,18 @@ fn main() {
         WindowResolution::default()
     };
 
+    let sensor_report = if args.raw_sensor_distances {
+        SensorReport::RawDistance
+    } else {
+        SensorReport::Firmware
+    };
+
+    let frame_pipeline = if args.degraded_frames {
+        FramePipeline::jetson_csi()
+    } else {
+        FramePipeline::clean()
+    };
+
     let mut mmap = unsafe {
         memmap::MmapOptions::new()
             .map_mut(&file)
@@ -74,6 +108,11 @@ fn main() {
         .add_plugins((
             SceneLoaderPlugin {
                 // scene_data: SceneData::deposit_test(),
+                // scene_data: SceneData::dunes(),
+                // scene_data: SceneData::patches(),
+                // scene_data: SceneData::beach(),
+                // scene_data: SceneData::litter(),
+                // scene_data: SceneData::distractors(),
                 scene_data: SceneData::default(),
                 draw_can_collision_sphere: false,
                 draw_obstacle_collision_mesh: false,
@@ -81,8 +120,15 @@ fn main() {
             ArenitoPlugin {
                 enable_can_eating: true,
                 // arenito_config: ArenitoConfig::deposit_test(args.visible_cameras),
-                arenito_config: ArenitoConfig::default(),
+                arenito_config: ArenitoConfig {
+                    sensor_report,
+                    ideal_sensors: args.ideal_sensors,
+                    frame_pipeline,
+                    visible_cameras: args.visible_cameras,
+                    ..default()
+                },
             },
+            EpisodeStatsPlugin,
         ))
         .run();
 }
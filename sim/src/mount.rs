@@ -0,0 +1,165 @@
+use bevy::prelude::*;
+
+/// Servo driven pan/tilt mount a camera sits on.
+///
+/// Like hobby servos, it moves at a limited rate towards where it's
+/// pointed, and then takes a moment to settle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraMount {
+    // where the mount faces, yaw is relative to it (radians)
+    heading: f32,
+    // current angles (radians), negative pitch looks down
+    pitch: f32,
+    yaw: f32,
+    target_pitch: f32,
+    target_yaw: f32,
+    /// Max speed, in rad/s.
+    pub rate: f32,
+    /// Time it takes to settle once it gets there, in seconds.
+    pub settle_time: f32,
+    pitch_limits: (f32, f32),
+    yaw_limits: (f32, f32),
+    // time left until it's settled
+    settling: f32,
+}
+
+impl CameraMount {
+    // MG90S: ~0.1 s/60°, slowed down by the camera's weight
+    const DEFAULT_RATE: f32 = 4.0;
+    const DEFAULT_SETTLE_TIME: f32 = 0.15;
+
+    /// A mount facing `heading` (about Arenito's y axis, 0 is forward),
+    /// pitched `pitch` radians.
+    pub fn new(heading: f32, pitch: f32) -> Self {
+        CameraMount {
+            heading,
+            pitch,
+            yaw: 0.0,
+            target_pitch: pitch,
+            target_yaw: 0.0,
+            rate: Self::DEFAULT_RATE,
+            settle_time: Self::DEFAULT_SETTLE_TIME,
+            pitch_limits: (-std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_6),
+            yaw_limits: (-std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2),
+            settling: 0.0,
+        }
+    }
+
+    /// Limits the pitch and yaw (relative to the heading) the mount can reach.
+    pub fn with_limits(mut self, pitch: (f32, f32), yaw: (f32, f32)) -> Self {
+        self.pitch_limits = pitch;
+        self.yaw_limits = yaw;
+        self.point(self.target_pitch, self.target_yaw);
+        self.pitch = self.target_pitch;
+        self.yaw = self.target_yaw;
+        self
+    }
+
+    pub fn with_rate(mut self, rate: f32, settle_time: f32) -> Self {
+        self.rate = rate;
+        self.settle_time = settle_time;
+        self
+    }
+
+    /// Points the mount, angles are clamped to its limits.
+    pub fn point(&mut self, pitch: f32, yaw: f32) {
+        self.target_pitch = pitch.clamp(self.pitch_limits.0, self.pitch_limits.1);
+        self.target_yaw = yaw.clamp(self.yaw_limits.0, self.yaw_limits.1);
+    }
+
+    /// Moves the servos, given some time in seconds.
+    pub fn update(&mut self, delta: f32) {
+        if self.is_moving() {
+            let step = self.rate * delta;
+            let approach = |from: f32, to: f32| {
+                if (to - from).abs() <= step {
+                    to
+                } else {
+                    from + step.copysign(to - from)
+                }
+            };
+            self.pitch = approach(self.pitch, self.target_pitch);
+            self.yaw = approach(self.yaw, self.target_yaw);
+            self.settling = self.settle_time;
+        } else {
+            self.settling = (self.settling - delta).max(0.0);
+        }
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.pitch != self.target_pitch || self.yaw != self.target_yaw
+    }
+
+    /// Whether the mount got where it was pointed, and stopped shaking.
+    pub fn is_settled(&self) -> bool {
+        !self.is_moving() && self.settling <= 0.0
+    }
+
+    /// Current (pitch, yaw), yaw relative to the mount's heading.
+    pub fn angles(&self) -> (f32, f32) {
+        (self.pitch, self.yaw)
+    }
+
+    /// Camera's rotation relative to Arenito: it looks along its x axis.
+    pub fn rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.heading + self.yaw) * Quat::from_rotation_z(self.pitch)
+    }
+}
+
+#[cfg(test)]
+mod camera_mount_tests {
+    use super::*;
+
+    #[test]
+    fn test_mount_is_rate_limited() {
+        let mut mount = CameraMount::new(0.0, 0.0).with_rate(1.0, 0.1);
+        mount.point(-0.5, 0.3);
+        mount.update(0.2);
+
+        assert_eq!(mount.angles(), (-0.2, 0.2));
+        assert!(mount.is_moving());
+
+        mount.update(0.2);
+        assert_eq!(mount.angles(), (-0.4, 0.3));
+    }
+
+    #[test]
+    fn test_mount_settles_after_moving() {
+        let mut mount = CameraMount::new(0.0, 0.0).with_rate(1.0, 0.1);
+        assert!(mount.is_settled());
+
+        mount.point(0.1, 0.0);
+        mount.update(0.2);
+        assert!(!mount.is_moving());
+        assert!(!mount.is_settled());
+
+        mount.update(0.05);
+        assert!(!mount.is_settled());
+        mount.update(0.06);
+        assert!(mount.is_settled());
+    }
+
+    #[test]
+    fn test_mount_clamps_to_limits() {
+        let mut mount = CameraMount::new(0.0, 0.0).with_limits((-1.0, 0.0), (-0.5, 0.5));
+        mount.point(1.0, -2.0);
+        mount.update(10.0);
+
+        assert_eq!(mount.angles(), (0.0, -0.5));
+    }
+
+    #[test]
+    fn test_mount_rotation() {
+        let down = CameraMount::new(0.0, -std::f32::consts::FRAC_PI_2);
+        let back = CameraMount::new(std::f32::consts::PI, 0.0);
+
+        assert!(down
+            .rotation()
+            .mul_vec3(Vec3::X)
+            .abs_diff_eq(-Vec3::Y, 1e-6));
+        assert!(back
+            .rotation()
+            .mul_vec3(Vec3::X)
+            .abs_diff_eq(-Vec3::X, 1e-6));
+    }
+}
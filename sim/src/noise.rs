@@ -0,0 +1,231 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+/// One stage of a sensor's noise.
+///
+/// Stages are stacked in a `SensorNoise`, each one takes the previous
+/// one's output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoiseModel {
+    /// Zero-mean gaussian noise.
+    Gaussian { std_dev: f32 },
+    /// Gaussian noise that grows with the value (`std_dev` is a fraction of it).
+    Relative { std_dev: f32 },
+    /// Noise uniformly distributed in min..max.
+    Uniform { min: f32, max: f32 },
+    /// Constant offset (a badly calibrated sensor).
+    Bias(f32),
+    /// Offset that drifts `step` (std dev) every read, up to `limit` either way.
+    RandomWalk { step: f32, limit: f32 },
+    /// Rounds to multiples of the sensor's resolution.
+    Quantization(f32),
+    /// The read gets lost with this probability.
+    Dropout(f32),
+    /// The read is off by `magnitude` with this probability.
+    Spike { probability: f32, magnitude: f32 },
+}
+
+/// Returns a sample of the standard normal distribution (Box-Muller).
+fn gaussian(rng: &mut impl Rng) -> f32 {
+    let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+    let u2: f32 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+}
+
+/// A sensor's noise: a stack of `NoiseModel`s, plus the state they drag
+/// between reads (random walks).
+///
+/// Multi-axis sensors (like the IMU) read each axis through its own channel,
+/// so every axis drifts on its own.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SensorNoise {
+    pub models: Vec<NoiseModel>,
+    walks: Vec<f32>, // per channel
+}
+
+impl SensorNoise {
+    pub fn new(models: Vec<NoiseModel>) -> Self {
+        SensorNoise {
+            models,
+            walks: Vec::new(),
+        }
+    }
+
+    /// No noise at all, the sensor reads the real value.
+    pub fn ideal() -> Self {
+        SensorNoise::default()
+    }
+
+    pub fn with(mut self, model: NoiseModel) -> Self {
+        self.models.push(model);
+        self
+    }
+
+    pub fn is_ideal(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    /// Returns what the sensor reads, given the real `value`.
+    /// Returns None if the read was lost.
+    pub fn apply(&mut self, value: f32, rng: &mut impl Rng) -> Option<f32> {
+        self.apply_channel(value, 0, rng)
+    }
+
+    /// Applies the noise to each axis, the whole read is lost if any is.
+    pub fn apply_vec3(&mut self, value: Vec3, rng: &mut impl Rng) -> Option<Vec3> {
+        Some(Vec3::new(
+            self.apply_channel(value.x, 0, rng)?,
+            self.apply_channel(value.y, 1, rng)?,
+            self.apply_channel(value.z, 2, rng)?,
+        ))
+    }
+
+    /// Like `apply`, on one of the sensor's channels (axes, colors...).
+    pub fn apply_channel(&mut self, value: f32, channel: usize, rng: &mut impl Rng) -> Option<f32> {
+        if self.walks.len() <= channel {
+            self.walks.resize(channel + 1, 0.0);
+        }
+
+        let mut value = value;
+        for model in self.models.iter() {
+            value = match *model {
+                NoiseModel::Gaussian { std_dev } => value + std_dev * gaussian(rng),
+                NoiseModel::Relative { std_dev } => value * (1.0 + std_dev * gaussian(rng)),
+                NoiseModel::Uniform { min, max } if min < max => value + rng.gen_range(min..max),
+                NoiseModel::Uniform { .. } => value,
+                NoiseModel::Bias(bias) => value + bias,
+                NoiseModel::RandomWalk { step, limit } => {
+                    let walk = &mut self.walks[channel];
+                    *walk = (*walk + step * gaussian(rng)).clamp(-limit, limit);
+                    value + *walk
+                }
+                NoiseModel::Quantization(step) if step > 0.0 => (value / step).round() * step,
+                NoiseModel::Quantization(_) => value,
+                NoiseModel::Dropout(probability) => {
+                    if rng.gen_bool(probability.clamp(0.0, 1.0) as f64) {
+                        return None;
+                    }
+                    value
+                }
+                NoiseModel::Spike {
+                    probability,
+                    magnitude,
+                } => {
+                    if rng.gen_bool(probability.clamp(0.0, 1.0) as f64) {
+                        value + magnitude
+                    } else {
+                        value
+                    }
+                }
+            };
+        }
+
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod sensor_noise_tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn stats(noise: &mut SensorNoise, value: f32) -> (f32, f32) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let reads: Vec<f32> = (0..5000)
+            .map(|_| noise.apply(value, &mut rng).unwrap())
+            .collect();
+        let mean = reads.iter().sum::<f32>() / reads.len() as f32;
+        let var = reads.iter().map(|r| (r - mean).powi(2)).sum::<f32>() / reads.len() as f32;
+        (mean, var.sqrt())
+    }
+
+    #[test]
+    fn test_ideal_reads_real_value() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut noise = SensorNoise::ideal();
+
+        assert!(noise.is_ideal());
+        assert_eq!(noise.apply(1.234, &mut rng), Some(1.234));
+    }
+
+    #[test]
+    fn test_gaussian_std_dev() {
+        let mut noise = SensorNoise::new(vec![NoiseModel::Gaussian { std_dev: 0.1 }]);
+        let (mean, std_dev) = stats(&mut noise, 2.0);
+
+        assert!((mean - 2.0).abs() < 0.01);
+        assert!((std_dev - 0.1).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_uniform_stays_in_range() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut noise = SensorNoise::new(vec![NoiseModel::Uniform {
+            min: -0.05,
+            max: 0.05,
+        }]);
+
+        for _ in 0..1000 {
+            let read = noise.apply(1.0, &mut rng).unwrap();
+            assert!((0.95..1.05).contains(&read));
+        }
+    }
+
+    #[test]
+    fn test_bias_then_quantization() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut noise = SensorNoise::ideal()
+            .with(NoiseModel::Bias(0.12))
+            .with(NoiseModel::Quantization(0.25));
+
+        assert_eq!(noise.apply(1.0, &mut rng), Some(1.0));
+        assert_eq!(noise.apply(1.1, &mut rng), Some(1.25));
+    }
+
+    #[test]
+    fn test_random_walk_drifts_within_limit() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut noise = SensorNoise::new(vec![NoiseModel::RandomWalk {
+            step: 0.05,
+            limit: 0.2,
+        }]);
+
+        let reads: Vec<f32> = (0..500)
+            .map(|_| noise.apply(0.0, &mut rng).unwrap())
+            .collect();
+        assert!(reads.iter().all(|r| r.abs() <= 0.2));
+        // consecutive reads are close, unlike white noise
+        assert!(reads.windows(2).all(|w| (w[1] - w[0]).abs() < 0.3));
+        assert!(reads.iter().any(|r| r.abs() > 0.05));
+    }
+
+    #[test]
+    fn test_channels_drift_independently() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut noise = SensorNoise::new(vec![NoiseModel::RandomWalk {
+            step: 0.05,
+            limit: 1.0,
+        }]);
+
+        let mut read = Vec3::ZERO;
+        for _ in 0..50 {
+            read = noise.apply_vec3(Vec3::ZERO, &mut rng).unwrap();
+        }
+        assert_ne!(read.x, read.y);
+        assert_ne!(read.y, read.z);
+    }
+
+    #[test]
+    fn test_dropouts_and_spikes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut dropout = SensorNoise::new(vec![NoiseModel::Dropout(1.0)]);
+        let mut spike = SensorNoise::new(vec![NoiseModel::Spike {
+            probability: 1.0,
+            magnitude: -0.5,
+        }]);
+
+        assert_eq!(dropout.apply(1.0, &mut rng), None);
+        assert_eq!(dropout.apply_vec3(Vec3::ONE, &mut rng), None);
+        assert_eq!(spike.apply(1.0, &mut rng), Some(0.5));
+    }
+}
@@ -1,7 +1,12 @@
 use crate::cans::*;
 use crate::collision::*;
+use crate::debris::*;
+use crate::deposit::*;
+use crate::stats::EpisodeStats;
+use crate::terrain::*;
 use bevy::{prelude::*, render::view::RenderLayers};
 use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
+use rand::Rng;
 
 pub struct SceneLoaderPlugin {
     pub scene_data: SceneData,
@@ -13,18 +18,33 @@ impl Plugin for SceneLoaderPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(PanOrbitCameraPlugin)
             .insert_resource(CanManager::new())
+            .insert_resource(ObstacleGrid::default())
+            .insert_resource(CanRespawner::new(self.scene_data.respawn_policy))
             .insert_resource(self.scene_data.clone())
+            .insert_resource(
+                Terrain::new(
+                    self.scene_data.terrain,
+                    self.scene_data.sand.length,
+                    self.scene_data.sand.width,
+                )
+                .with_materials(self.scene_data.material_zones.clone())
+                .with_shoreline(self.scene_data.shoreline),
+            )
             .add_systems(
                 PreStartup,
                 (init_can_manager, generate_scene.after(init_can_manager)),
+            )
+            .add_systems(PreUpdate, (compute_hulls, update_obstacle_grid).chain())
+            .add_systems(
+                Update,
+                (score_dumped_cans, respawn_cans, lose_cans_in_water),
             );
 
         if self.draw_can_collision_sphere {
-            app.add_systems(Update, (draw_can_collision_sphere, respawn_cans));
+            app.add_systems(Update, draw_can_collision_sphere);
         }
         if self.draw_obstacle_collision_mesh {
-            app.add_systems(PreUpdate, compute_hulls);
-            app.add_systems(Update, draw_obstacle_collision_mesh);
+            app.add_systems(Update, (draw_obstacle_collision_mesh, draw_deposit_volume));
         }
     }
 }
@@ -71,6 +91,12 @@ pub struct SceneData {
     can_positions: Vec<(f32, f32, f32)>,
     deposit_position: Vec3,
     obstacles: Vec<ObstacleData>,
+    terrain: TerrainSource,
+    material_zones: Vec<MaterialZone>,
+    shoreline: Option<Shoreline>,
+    can_variety: CanVariety,
+    debris: Vec<DebrisData>,
+    respawn_policy: RespawnPolicy,
 }
 
 impl SceneData {
@@ -89,6 +115,94 @@ impl SceneData {
         self
     }
 
+    /// Sets where the sand's heights come from.
+    pub fn terrain(mut self, terrain: TerrainSource) -> Self {
+        self.terrain = terrain;
+        self
+    }
+
+    /// Sets the ground material zones.
+    pub fn materials(mut self, material_zones: Vec<MaterialZone>) -> Self {
+        self.material_zones = material_zones;
+        self
+    }
+
+    /// Sets the shoreline.
+    pub fn shoreline(mut self, shoreline: Shoreline) -> Self {
+        self.shoreline = Some(shoreline);
+        self
+    }
+
+    /// Sets which kinds of cans are spawned.
+    pub fn can_variety(mut self, can_variety: CanVariety) -> Self {
+        self.can_variety = can_variety;
+        self
+    }
+
+    /// Sets when and where cans are respawned.
+    pub fn respawn_policy(mut self, respawn_policy: RespawnPolicy) -> Self {
+        self.respawn_policy = respawn_policy;
+        self
+    }
+
+    /// Sets the debris (things that aren't cans) on the sand.
+    pub fn debris(mut self, debris: Vec<DebrisData>) -> Self {
+        self.debris = debris;
+        self
+    }
+
+    /// Default scene, with debris scattered among the cans.
+    pub fn distractors() -> Self {
+        SceneData::litter().debris(vec![
+            (DebrisKind::Bottle, 2.0, 1.5, 0.4),
+            (DebrisKind::Bottle, -6.0, -2.5, 2.2),
+            (DebrisKind::BottleCap, 4.0, -1.2, 0.0),
+            (DebrisKind::BottleCap, -3.1, 0.2, 0.0),
+            (DebrisKind::Shell, 6.5, 2.8, 1.0),
+            (DebrisKind::Shell, -0.5, -3.8, 2.5),
+            (DebrisKind::Seaweed, 8.0, -2.0, 0.7),
+            (DebrisKind::Seaweed, -9.5, 1.5, 1.9),
+            (DebrisKind::DarkRock, 0.5, 4.5, 0.3),
+            (DebrisKind::DarkRock, -4.0, 5.5, 1.2),
+            (DebrisKind::Footprint, 1.6, -1.0, 0.2),
+            (DebrisKind::Footprint, 2.0, -0.7, 0.2),
+            (DebrisKind::Footprint, 2.4, -1.0, 0.2),
+            (DebrisKind::Footprint, 2.8, -0.7, 0.2),
+            (DebrisKind::Towel, 7.0, 5.5, 0.1),
+        ])
+    }
+
+    /// Default scene, littered with all kinds of cans.
+    pub fn litter() -> Self {
+        SceneData::default().can_variety(CanVariety::beach_litter(33))
+    }
+
+    /// Default scene, with an irregular shoreline and shallow water.
+    pub fn beach() -> Self {
+        SceneData::default()
+            .shoreline(Shoreline::default())
+            .base_size(15.0, 25.0, 8.0)
+    }
+
+    /// Default scene, with patches of different kinds of ground.
+    pub fn patches() -> Self {
+        SceneData::default().materials(vec![
+            MaterialZone::new(GroundMaterial::WetSand, 6.0, 2.5, 2.0),
+            MaterialZone::new(GroundMaterial::PackedSand, -3.5, -2.0, 2.5),
+            MaterialZone::new(GroundMaterial::Pebbles, 1.5, -4.5, 1.5),
+            MaterialZone::new(GroundMaterial::WetSand, -9.0, 3.0, 1.8),
+        ])
+    }
+
+    /// Default scene, over procedurally generated dunes.
+    pub fn dunes() -> Self {
+        SceneData::default().terrain(TerrainSource::Noise {
+            seed: 26,
+            amplitude: 0.6,
+            frequency: 0.15,
+        })
+    }
+
     pub fn deposit_test() -> Self {
         SceneData {
             cam_transform: Transform::from_xyz(0.0, 15.0, 0.01).looking_at(Vec3::ZERO, Vec3::Y),
@@ -97,6 +211,12 @@ impl SceneData {
             can_positions: vec![(0.0, 0.0, 0.6)],
             deposit_position: Vec3::new(3.0, 0.0, -3.0),
             obstacles: Vec::new(),
+            terrain: TerrainSource::Flat,
+            material_zones: Vec::new(),
+            shoreline: None,
+            can_variety: CanVariety::default(),
+            debris: Vec::new(),
+            respawn_policy: RespawnPolicy::default(),
         }
     }
 }
@@ -138,7 +258,15 @@ impl Default for SceneData {
                     0.5,
                     0.0,
                 )),
+                // sensors can see between the chair's legs
+                proxy: ObstacleProxy::Mesh,
             }],
+            terrain: TerrainSource::Flat,
+            material_zones: Vec::new(),
+            shoreline: None,
+            can_variety: CanVariety::default(),
+            debris: Vec::new(),
+            respawn_policy: RespawnPolicy::default(),
         }
     }
 }
@@ -193,6 +321,8 @@ impl PlaneData {
 pub struct ObstacleData {
     models: Vec<(&'static str, TextureOrColor)>,
     transform: Transform,
+    /// What the models' hulls are made of.
+    proxy: ObstacleProxy,
 }
 
 fn generate_scene(
@@ -202,12 +332,25 @@ fn generate_scene(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut can_manager: ResMut<CanManager>,
+    terrain: Res<Terrain>,
 ) {
     // spawn base
-    let water_material = scene_data.water.get_material(&asset_server);
+    let mut water_material = scene_data.water.get_material(&asset_server);
     let water_scale = Vec3::new(scene_data.water.length, 1.0, scene_data.water.width);
     let sand_material = scene_data.sand.get_material(&asset_server);
-    let sand_scale = Vec3::new(scene_data.sand.length, 1.0, scene_data.sand.width);
+
+    if scene_data.shoreline.is_some() {
+        // shallow water: see-through, with sea bed under it
+        water_material.base_color.set_a(0.65);
+        water_material.alpha_mode = AlphaMode::Blend;
+
+        commands.spawn(PbrBundle {
+            mesh: meshes.add(shape::Plane::from_size(1.0).into()),
+            material: materials.add(Color::rgb(0.35, 0.3, 0.2).into()),
+            transform: Transform::from_xyz(0.0, -0.6, 0.0).with_scale(water_scale),
+            ..default()
+        });
+    }
 
     commands.spawn(PbrBundle {
         mesh: meshes.add(shape::Plane::from_size(1.0).into()),
@@ -217,9 +360,8 @@ fn generate_scene(
     });
 
     commands.spawn(PbrBundle {
-        mesh: meshes.add(shape::Plane::from_size(1.0).into()),
+        mesh: meshes.add(Mesh::from(terrain.as_ref())),
         material: materials.add(sand_material),
-        transform: Transform::from_xyz(0.0, 0.01, 0.0).with_scale(sand_scale),
         ..default()
     });
 
@@ -271,43 +413,45 @@ fn generate_scene(
     ));
 
     // spawn cans
-    for d in scene_data.can_positions.iter() {
-        let (x, z, ry) = d;
-
-        can_manager.spawn(
-            &mut commands,
-            CanData::default(),
-            Transform::from_xyz(*x, 0.2, *z).with_rotation(Quat::from_euler(
-                EulerRot::XYZ,
-                0.0,
-                *ry,
-                1.56,
-            )),
-        );
+    spawn_cans(&mut can_manager, &mut commands, &scene_data, &terrain);
+
+    // spawn debris
+    for (kind, x, z, ry) in scene_data.debris.iter() {
+        let debris = Debris { kind: *kind };
+        let ground = terrain.height_at(*x, *z);
+        let transform = debris.placement(*x, *z, *ry, ground);
+        debris.spawn(&mut commands, &mut meshes, &mut materials, transform);
     }
 
     // spawn deposit
+    let mut deposit_position = scene_data.deposit_position;
+    deposit_position.y += terrain.height_at(deposit_position.x, deposit_position.z);
     commands.spawn((
         PbrBundle {
             mesh: asset_server.load("models/deposit.obj"),
             material: materials.add(Color::RED.into()),
-            transform: Transform::from_translation(scene_data.deposit_position),
+            transform: Transform::from_translation(deposit_position),
             ..default()
         },
-        Obstacle::empty(),
+        Obstacle::empty().with_layers(CollisionLayers::DEPOSIT),
+        Deposit,
     ));
 
     // spawn obstacles
     for obstacle in scene_data.obstacles.iter() {
+        let mut transform = obstacle.transform;
+        transform.translation.y +=
+            terrain.height_at(transform.translation.x, transform.translation.z);
+
         for model in obstacle.models.iter() {
             commands.spawn((
                 PbrBundle {
                     mesh: asset_server.load(model.0),
                     material: materials.add(model.1.get_material(0.3, &asset_server)),
-                    transform: obstacle.transform.clone(),
+                    transform,
                     ..default()
                 },
-                Obstacle::empty(),
+                Obstacle::empty().with_proxy(obstacle.proxy.clone()),
             ));
         }
     }
@@ -329,29 +473,118 @@ pub fn draw_obstacle_collision_mesh(mut gizmos: Gizmos, obstacles: Query<&Obstac
     }
 }
 
+/// Respawns cans, following the current `RespawnPolicy`.
+/// Pressing `C` always respawns them.
 fn respawn_cans(
+    time: Res<Time>,
+    mut respawner: ResMut<CanRespawner>,
     mut can_manager: ResMut<CanManager>,
     mut commands: Commands,
     scene_data: Res<SceneData>,
     keyboard_input: Res<Input<KeyCode>>,
+    terrain: Res<Terrain>,
 ) {
     if keyboard_input.just_pressed(KeyCode::C) {
-        for d in scene_data.can_positions.iter() {
-            let (x, z, ry) = d;
-
-            can_manager.spawn(
-                &mut commands,
-                CanData {
-                    size: CanSize::Big,
-                    texture: CanTexture::Shiny,
-                },
-                Transform::from_xyz(*x, 0.2, *z).with_rotation(Quat::from_euler(
-                    EulerRot::XYZ,
-                    0.0,
-                    *ry,
-                    1.56,
-                )),
-            );
+        respawner.request();
+    }
+
+    if !respawner.should_respawn(time.delta(), can_manager.count(CanState::OnSand)) {
+        return;
+    }
+
+    match respawner.policy().placement {
+        RespawnPlacement::Original => {
+            spawn_cans(&mut can_manager, &mut commands, &scene_data, &terrain)
+        }
+        RespawnPlacement::Random => {
+            spawn_random_cans(&mut can_manager, &mut commands, &scene_data, &terrain)
+        }
+    }
+}
+
+/// Spawns the scene's cans, drawn from its can variety, skipping the
+/// positions that still have a can on them.
+/// The same cans are drawn every time.
+fn spawn_cans(
+    can_manager: &mut CanManager,
+    commands: &mut Commands,
+    scene_data: &SceneData,
+    terrain: &Terrain,
+) {
+    let mut rng = scene_data.can_variety.rng();
+
+    for (slot, d) in scene_data.can_positions.iter().enumerate() {
+        let (x, z, ry) = *d;
+        let can = scene_data.can_variety.sample(&mut rng);
+        if can_manager.slot_taken(slot) {
+            continue;
+        }
+
+        let ground = terrain.height_at(x, z);
+        can_manager.spawn(commands, can, can.placement(x, z, ry, ground), Some(slot));
+    }
+}
+
+/// Spawns cans anywhere on the sand, until there are as many cans
+/// on the sand as the scene has.
+fn spawn_random_cans(
+    can_manager: &mut CanManager,
+    commands: &mut Commands,
+    scene_data: &SceneData,
+    terrain: &Terrain,
+) {
+    // keep away from the sand's border
+    const MARGIN: f32 = 0.9;
+    // tries per can, in case (almost) all the sand is under water
+    const ATTEMPTS_PER_CAN: usize = 100;
+
+    let mut rng = rand::thread_rng();
+    let missing = scene_data
+        .can_positions
+        .len()
+        .saturating_sub(can_manager.count(CanState::OnSand));
+    let half_length = scene_data.sand.length * MARGIN / 2.0;
+    let half_width = scene_data.sand.width * MARGIN / 2.0;
+
+    let mut spawned = 0;
+    for _ in 0..missing * ATTEMPTS_PER_CAN {
+        if spawned == missing {
+            break;
+        }
+        let x = rng.gen_range(-half_length..half_length);
+        let z = rng.gen_range(-half_width..half_width);
+        if terrain.is_water(x, z) {
+            continue;
+        }
+
+        let can = scene_data.can_variety.sample(&mut rng);
+        let ry = rng.gen_range(0.0..std::f32::consts::TAU);
+        let ground = terrain.height_at(x, z);
+        can_manager.spawn(commands, can, can.placement(x, z, ry, ground), None);
+        spawned += 1;
+    }
+
+    if spawned < missing {
+        println!(
+            "Couldn't place {} cans out of the water!",
+            missing - spawned
+        );
+    }
+}
+
+/// Despawns the cans that went into the water.
+fn lose_cans_in_water(
+    mut commands: Commands,
+    mut can_manager: ResMut<CanManager>,
+    mut stats: ResMut<EpisodeStats>,
+    terrain: Res<Terrain>,
+    cans: Query<(Entity, &CanId, &Transform)>,
+) {
+    for (ent, id, transform) in cans.iter() {
+        if terrain.is_water(transform.translation.x, transform.translation.z) {
+            commands.entity(ent).despawn_recursive();
+            can_manager.lose(*id);
+            stats.lost_cans += 1;
         }
     }
 }
@@ -1,8 +1,16 @@
-use crate::{arenito::*, collision::*};
-use bevy::{prelude::*, render::view::screenshot::ScreenshotManager};
-use image::imageops::FilterType;
+use crate::{
+    arenito::*,
+    cans::{RespawnPlacement, RespawnPolicy, RespawnTrigger},
+    collision::{Ray, *},
+    frame::{CameraSettings, Capture, FramePipeline},
+    noise::{NoiseModel, SensorNoise},
+    static_shape::CameraGeometry,
+    terrain::Terrain,
+};
+use bevy::prelude::*;
+use image::{imageops::FilterType, DynamicImage, RgbImage};
 use memmap::MmapMut;
-use rand::{prelude::thread_rng, Rng};
+use rand::Rng;
 use std::{
     fs::{File, OpenOptions},
     io::{Seek, SeekFrom, Write},
@@ -28,36 +36,29 @@ impl FromGyro for Vec3 {
     }
 }
 
-/// This struct's purpose is to generalize "Error Vector" generation.
-/// Since this is a needed step on simulating sensors.
-struct SensorError;
-impl SensorError {
-    const DEFAULT_MIN: f32 = -0.05;
-    const DEFAULT_MAX: f32 = 0.05;
-
-    /// Returns a Vec3 with random values in the range min..max.
-    pub fn vec(min: f32, max: f32) -> Vec3 {
-        let mut rng = thread_rng();
-        Vec3::new(
-            rng.gen_range(min..max),
-            rng.gen_range(min..max),
-            rng.gen_range(min..max),
-        )
-    }
-
-    /// Returns the default (kinda like std::default::Default) vector
-    /// with an error range DEFAULT_MIN..DEFAULT_MAX.
-    pub fn default() -> Vec3 {
-        SensorError::vec(SensorError::DEFAULT_MIN, SensorError::DEFAULT_MAX)
-    }
-}
-
 /// This struct is responsible for the simulations of all sensors related to Arenito.
 /// This sensor simulation is based on the MPU6050 Chip, which includes an acceleromter
 /// and a gyroscope.
 /// The outputs of this simulator are trying to be as similar as posible to this:
 /// (https://randomnerdtutorials.com/arduino-mpu-6050-accelerometer-gyroscope/)
-pub struct MPU6050;
+#[derive(Clone, Debug)]
+pub struct MPU6050 {
+    pub acc_noise: SensorNoise,
+    pub rot_noise: SensorNoise,
+}
+
+impl Default for MPU6050 {
+    fn default() -> Self {
+        let noise = SensorNoise::new(vec![NoiseModel::Uniform {
+            min: -0.05,
+            max: 0.05,
+        }]);
+        MPU6050 {
+            acc_noise: noise.clone(),
+            rot_noise: noise,
+        }
+    }
+}
 
 impl MPU6050 {
     // This is the upper bound for the accelerometer readings.
@@ -65,17 +66,26 @@ impl MPU6050 {
     // Acceleration reads CAN NOT be higher than this.
     pub const ACCELERATION_MAX: f32 = 9.8; // 1g!
 
+    /// Noiseless IMU.
+    pub fn ideal() -> Self {
+        MPU6050 {
+            acc_noise: SensorNoise::ideal(),
+            rot_noise: SensorNoise::ideal(),
+        }
+    }
+
     /// Gets Arenito's "real" acceleration and converts it
     /// to something the real accelerometer would return:
     /// A value between 0 and 1024 that represents the magnitude
     /// of the acceleration on each axis.
-    pub fn read_acc(arenito: &Arenito) -> Vec3 {
+    /// Lost reads read 0.
+    pub fn read_acc(&mut self, arenito: &Arenito, rng: &mut impl Rng) -> Vec3 {
         // get acceleration value
-        // convert to absolute value
-        let mut acc = arenito.acc.abs();
-
-        // add error
-        acc += SensorError::default();
+        // convert to absolute value, and add error
+        let acc = self
+            .acc_noise
+            .apply_vec3(arenito.acc.abs(), rng)
+            .unwrap_or(Vec3::ZERO);
 
         // interpolate each value between [0, 1024],
         // considering that Sensor::ACCELERATION_MAX maps to 1024.
@@ -87,8 +97,9 @@ impl MPU6050 {
     /// too lazy to simulate that.
     /// This implementation skips all the math needed to convert
     /// from rotational speed to "current rotation" altogether.
-    pub fn read_rot(transform: &Transform) -> Vec3 {
-        transform.rotation.mul_vec3(Vec3::X) + SensorError::default()
+    pub fn read_rot(&mut self, transform: &Transform, rng: &mut impl Rng) -> Vec3 {
+        let rot = transform.rotation.mul_vec3(Vec3::X);
+        self.rot_noise.apply_vec3(rot, rng).unwrap_or(Vec3::ZERO)
     }
 }
 
@@ -101,13 +112,26 @@ pub enum SimInstruction {
     MoveRight,
     MoveLongRight,
     Evade,
-    FrontCamFrame,
-    RearCamFrame,
+    CameraFrame(u8),
     ProxSensorReads,
     DumpCans(u8),
+    HopperCount,
+    BackdoorSwitches,
+    ExtendBackdoor,
+    RespawnCans,
+    SetRespawnPolicy(RespawnPolicy),
+    SetCameraSettings(u8, CameraSettings),
+    PointCamera {
+        camera: u8,
+        pitch: f32,
+        yaw: f32,
+    },
+    CameraGeometry(u8),
     BrushOn,
     BrushOff,
     StopAll,
+    /// Couldn't be read, it's acknowledged and ignored.
+    Ignored,
 }
 
 /// Wrapper struct to store raw pointers to shared memory.
@@ -166,6 +190,17 @@ impl AISimAddr {
 /// When sync is AI_MOVE_INSTRUCTION:
 ///   The next byte (second) is the movement instruction.
 ///
+/// When sync is AI_CAMERA_FRAME_REQUEST:
+///   The next byte is the camera's id (see `ArenitoConfig::cameras`).
+///
+/// When sync is AI_CAMERA_MOUNT:
+///   The camera's id, pitch and yaw (see `get_camera_mount_instruction`).
+///   Acknowledged once the mount settles.
+///
+/// When sync is AI_CAMERA_GEOMETRY_REQUEST:
+///   The next byte is the camera's id, answered with its geometry
+///   (see `export_camera_geometry`).
+///
 /// When sync is SIM_AKNOWLEDGE_INSTRUCTION, after AI_FRAME_REQUEST:
 ///   The following IMG_SIZE bytes are raw image data.
 /// The image sent is of size (1024, 1024).
@@ -184,6 +219,13 @@ impl AISimMem {
     const SIM_AKNOWLEDGE_INSTRUCTION: u8 = 4;
     const AI_PROX_SENSOR_READ_REQUEST: u8 = 5;
     const AI_DUMP_CANS: u8 = 7;
+    const AI_HOPPER_COUNT_REQUEST: u8 = 8;
+    const AI_BACKDOOR_SWITCHES_REQUEST: u8 = 9;
+    const AI_RESPAWN_CANS: u8 = 10;
+    const AI_CAMERA_SETTINGS: u8 = 11;
+    const AI_CAMERA_FRAME_REQUEST: u8 = 12;
+    const AI_CAMERA_MOUNT: u8 = 13;
+    const AI_CAMERA_GEOMETRY_REQUEST: u8 = 14;
     const AI_BRUSH_ON: u8 = b'P';
     const AI_BRUSH_OFF: u8 = b'p';
 
@@ -194,20 +236,22 @@ impl AISimMem {
     const MOV_BACK: u8 = b'r';
     const MOV_LONG_RIGHT: u8 = b'D';
     const STOP_ALL: u8 = b'S';
+    const EXTEND_BACKDOOR: u8 = b'e';
 
     // memory footprint
     // how much memory is used for synchronization
     const SYNC_SIZE: usize = 1;
     // min size required to store image, found experimentally
     const IMG_SIZE: usize = 786_432;
-    // how many bytes to allocate each sensor distance
-    // distances are a single byte: distance until collision, in cm
+    // each sensor read is a single byte (see `SensorEncoding`)
     // when sending sensor data, another byte, before sensor data
     // is set that indicates how many sensors there are.
-    pub const MAX_PROXIMITY_SENSOR_COUNT: usize = 7;
+    pub const MAX_PROXIMITY_SENSOR_COUNT: usize = 32;
     // total required memory
     pub const REQUIRED_MEMORY: usize = Self::SYNC_SIZE + Self::IMG_SIZE;
     pub const MMAP_FILENAME: &'static str = "file.mmap";
+    // exported frames are FRAME_SIZE x FRAME_SIZE
+    pub const FRAME_SIZE: u32 = 512;
 
     pub fn new(mmap: &mut MmapMut) -> Self {
         unsafe {
@@ -238,34 +282,22 @@ impl AISimMem {
         self.sync_byte.set(flag);
     }
 
-    /// Takes a screenshot of Arenito's Camera and writes it to the shared memory block.
-    pub fn export_frame(
-        &mut self,
-        screenshot_manager: &mut ResMut<ScreenshotManager>,
-        window: &Entity,
-    ) {
-        // prevent multiple screenshot requests
-        self.set_sync_flag(AISimMem::SIM_SCAN_WAIT);
-
-        // can't use directly `self.sync_byte`, thank you borrow checker.
-        let mut sync_byte = self.sync_byte.clone();
-        let mut memspace = self.memspace.clone();
-
-        let _ =
-            screenshot_manager.take_screenshot(*window, move |img| match img.try_into_dynamic() {
-                Ok(dyn_img) => {
-                    let img_raw = dyn_img
-                        .resize(512, 512, FilterType::Triangle)
-                        .to_rgb8()
-                        .into_raw();
-
-                    memspace.write(&img_raw);
-                    sync_byte.set(AISimMem::SIM_AKNOWLEDGE_INSTRUCTION);
-                }
-                Err(_) => {
-                    println!("Cannot send screenshot!")
-                }
-            });
+    /// Writes a frame of Arenito's camera to the shared memory block,
+    /// degraded by `pipeline` (as if taken as `capture` says).
+    pub fn export_frame(&mut self, img: RgbImage, mut pipeline: FramePipeline, capture: Capture) {
+        let img = DynamicImage::ImageRgb8(img)
+            .resize(
+                AISimMem::FRAME_SIZE,
+                AISimMem::FRAME_SIZE,
+                FilterType::Triangle,
+            )
+            .to_rgb8();
+        let img_raw = pipeline
+            .apply(img, &capture, &mut rand::thread_rng())
+            .into_raw();
+
+        self.memspace.write(&img_raw);
+        self.confirm_instruction();
     }
 
     /// Reads Arenito's proximity sensors and returns the value.
@@ -277,6 +309,49 @@ impl AISimMem {
         self.confirm_instruction();
     }
 
+    /// Writes how many cans are in Arenito's hopper.
+    pub fn export_hopper_count(&mut self, count: u8) {
+        self.memspace.set(count);
+        self.confirm_instruction();
+    }
+
+    /// Writes the backdoor's limit switches (`ls_up`, `ls_down`).
+    pub fn export_backdoor_switches(&mut self, (ls_up, ls_down): (bool, bool)) {
+        self.memspace.set(ls_up as u8);
+        self.memspace.next(1).set(ls_down as u8);
+        self.confirm_instruction();
+    }
+
+    /// Writes a camera's geometry (for exported frames), as little endian f32s:
+    /// - intrinsics: fx, fy, cx, cy, in pixels
+    /// - camera's position relative to Arenito, in cm
+    /// - camera to Arenito rotation, 3x3 row major
+    /// - pixel to ground homography, 3x3 row major, ground points in cm
+    ///
+    /// See `CameraGeometry` for the axes' conventions.
+    pub fn export_camera_geometry(&mut self, geometry: &CameraGeometry) {
+        // distances to cm
+        let to_cm = Mat3::from_diagonal(Vec3::new(
+            ProximitySensor::CM_PER_UNIT,
+            ProximitySensor::CM_PER_UNIT,
+            1.0,
+        ));
+        let rows = |m: Mat3| m.transpose().to_cols_array();
+
+        let values: Vec<f32> = geometry
+            .intrinsics
+            .to_array()
+            .into_iter()
+            .chain((geometry.position * ProximitySensor::CM_PER_UNIT).to_array())
+            .chain(rows(geometry.rotation))
+            .chain(rows(to_cm * geometry.homography))
+            .collect();
+        let bytes = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+        self.memspace.write(&bytes);
+        self.confirm_instruction();
+    }
+
     /// Returns the instruction for the simulation to execute.
     /// Returns None if there's none.
     ///
@@ -290,19 +365,32 @@ impl AISimMem {
     /// If sync byte is `AI_SCAN_REQUEST` no more bytes are checked.
     pub fn get_instruction(&self) -> Option<SimInstruction> {
         match self.sync_byte.get() {
-            AISimMem::AI_FRONT_CAM_REQUEST => Some(SimInstruction::FrontCamFrame),
-            AISimMem::AI_REAR_CAM_REQUEST => Some(SimInstruction::RearCamFrame),
+            AISimMem::AI_CAMERA_FRAME_REQUEST => {
+                Some(SimInstruction::CameraFrame(self.memspace.get()))
+            }
+            // front and rear cameras' own requests, from before cameras had ids
+            AISimMem::AI_FRONT_CAM_REQUEST => Some(SimInstruction::CameraFrame(0)),
+            AISimMem::AI_REAR_CAM_REQUEST => Some(SimInstruction::CameraFrame(1)),
             AISimMem::AI_PROX_SENSOR_READ_REQUEST => Some(SimInstruction::ProxSensorReads),
             AISimMem::AI_DUMP_CANS => Some(SimInstruction::DumpCans(self.memspace.get())),
+            AISimMem::AI_HOPPER_COUNT_REQUEST => Some(SimInstruction::HopperCount),
+            AISimMem::AI_BACKDOOR_SWITCHES_REQUEST => Some(SimInstruction::BackdoorSwitches),
+            AISimMem::AI_RESPAWN_CANS => Some(self.get_respawn_instruction()),
+            AISimMem::AI_CAMERA_SETTINGS => Some(self.get_camera_settings_instruction()),
+            AISimMem::AI_CAMERA_MOUNT => Some(self.get_camera_mount_instruction()),
+            AISimMem::AI_CAMERA_GEOMETRY_REQUEST => {
+                Some(SimInstruction::CameraGeometry(self.memspace.get()))
+            }
             AISimMem::AI_MOVE_INSTRUCTION => match self.memspace.get() {
-            AISimMem::AI_BRUSH_ON => Some(SimInstruction::BrushOn),
-            AISimMem::AI_BRUSH_OFF => Some(SimInstruction::BrushOff),
+                AISimMem::AI_BRUSH_ON => Some(SimInstruction::BrushOn),
+                AISimMem::AI_BRUSH_OFF => Some(SimInstruction::BrushOff),
                 AISimMem::MOV_FORWARD => Some(SimInstruction::MoveForward),
                 AISimMem::MOV_LEFT => Some(SimInstruction::MoveLeft),
                 AISimMem::MOV_RIGHT => Some(SimInstruction::MoveRight),
                 AISimMem::MOV_LONG_RIGHT => Some(SimInstruction::MoveLongRight),
                 AISimMem::MOV_BACK => Some(SimInstruction::MoveBack),
                 AISimMem::STOP_ALL => Some(SimInstruction::StopAll),
+                AISimMem::EXTEND_BACKDOOR => Some(SimInstruction::ExtendBackdoor),
                 other => {
                     println!("Unrecognized movement instruction '{}'", other);
                     None
@@ -312,6 +400,76 @@ impl AISimMem {
         }
     }
 
+    /// Reads a respawn instruction, memspace is:
+    /// - trigger: 0 manual, 1 respawn now, 2 when all cans are collected, 3 periodic
+    /// - placement: 0 original positions, 1 random positions
+    /// - period, in seconds (only used by periodic trigger, at least 1)
+    ///
+    /// Unrecognized values are ignored (but still acknowledged).
+    fn get_respawn_instruction(&self) -> SimInstruction {
+        if self.memspace.get() == 1 {
+            return SimInstruction::RespawnCans;
+        }
+
+        let placement = match self.memspace.next(1).get() {
+            0 => RespawnPlacement::Original,
+            1 => RespawnPlacement::Random,
+            other => {
+                println!("Unrecognized respawn placement '{}'", other);
+                return SimInstruction::Ignored;
+            }
+        };
+        let trigger = match self.memspace.get() {
+            0 => RespawnTrigger::Manual,
+            2 => RespawnTrigger::AllCollected,
+            3 => RespawnTrigger::Periodic(self.memspace.next(2).get().max(1) as f32),
+            other => {
+                println!("Unrecognized respawn trigger '{}'", other);
+                return SimInstruction::Ignored;
+            }
+        };
+
+        SimInstruction::SetRespawnPolicy(RespawnPolicy { trigger, placement })
+    }
+
+    /// Reads a camera settings instruction, memspace is:
+    /// - camera id (see `ArenitoConfig::cameras`)
+    /// - exposure, in 100 µs units like v4l2's `exposure_absolute` (two
+    ///   bytes, little endian), 0 for auto exposure
+    /// - gain, in 1/16ths (16 is no gain)
+    /// - white balance red, green and blue gains, in 1/128ths (128 is 1x)
+    fn get_camera_settings_instruction(&self) -> SimInstruction {
+        let byte = |i: usize| self.memspace.next(i).get();
+        let exposure = u16::from_le_bytes([byte(1), byte(2)]);
+
+        SimInstruction::SetCameraSettings(
+            byte(0),
+            CameraSettings {
+                exposure: (exposure > 0).then_some(exposure as f32 * 0.0001),
+                gain: byte(3) as f32 / 16.0,
+                white_balance: Vec3::new(byte(4) as f32, byte(5) as f32, byte(6) as f32) / 128.0,
+            },
+        )
+    }
+
+    /// Reads a camera mount instruction, memspace is:
+    /// - camera id (see `ArenitoConfig::cameras`)
+    /// - pitch, in tenths of a degree (two bytes, little endian, signed),
+    ///   negative looks down
+    /// - yaw, relative to where the mount faces, in tenths of a degree
+    ///   (two bytes, little endian, signed), positive turns left
+    fn get_camera_mount_instruction(&self) -> SimInstruction {
+        let byte = |i: usize| self.memspace.next(i).get();
+        let angle =
+            |i: usize| (i16::from_le_bytes([byte(i), byte(i + 1)]) as f32 / 10.0).to_radians();
+
+        SimInstruction::PointCamera {
+            camera: byte(0),
+            pitch: angle(1),
+            yaw: angle(3),
+        }
+    }
+
     /// Sets the sync flag to `SIM_AKNOWLEDGE_INSTRUCTION`.
     /// Indicates to the AI that the simulation is done processing the message and
     /// is ready to read another instruction.
@@ -320,94 +478,555 @@ impl AISimMem {
     pub fn confirm_instruction(&mut self) {
         self.sync_byte.set(AISimMem::SIM_AKNOWLEDGE_INSTRUCTION);
     }
+
+    /// Sets the sync flag to `SIM_SCAN_WAIT`.
+    /// Keeps the AI waiting for an instruction that takes several frames
+    /// to complete, `confirm_instruction` must be called once it's done.
+    pub fn hold_instruction(&mut self) {
+        self.set_sync_flag(AISimMem::SIM_SCAN_WAIT);
+    }
+}
+
+/// HC-SR04 style ultrasonic sensor.
+///
+/// Its beam is a cone (`cone_angle` radians from its axis), sampled with
+/// a ray along the axis and `rays` more around the cone's edge: the closest
+/// echo wins.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ultrasonic {
+    pub cone_angle: f32,
+    pub rays: usize,
+    pub min_range: f32,
+    pub max_range: f32,
+}
+
+impl Default for Ultrasonic {
+    fn default() -> Self {
+        Ultrasonic {
+            cone_angle: 15.0_f32.to_radians(),
+            rays: 6,
+            min_range: 0.06,
+            max_range: 3.0,
+        }
+    }
+}
+
+/// E18-D80NK style digital infrared sensor.
+///
+/// It triggers when something is closer than `threshold` (the sensor's
+/// adjustable screw), and doesn't release until it's `hysteresis` further.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Infrared {
+    pub threshold: f32,
+    pub hysteresis: f32,
+}
+
+impl Infrared {
+    pub fn new(threshold: f32) -> Self {
+        Infrared {
+            threshold,
+            hysteresis: threshold * 0.1,
+        }
+    }
+}
+
+/// How a proximity sensor senses, and what it outputs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProximityModel {
+    Ultrasonic(Ultrasonic),
+    Infrared(Infrared),
+}
+
+impl ProximityModel {
+    /// What the sensor can see: infrared light doesn't bounce back
+    /// from water, sound does.
+    pub fn default_layers(&self) -> CollisionLayers {
+        match self {
+            ProximityModel::Ultrasonic(_) => CollisionLayers::ALL,
+            ProximityModel::Infrared(_) => CollisionLayers::ALL.without(CollisionLayers::WATER),
+        }
+    }
+
+    /// How far off the real sensor reads: echoes are off by a couple
+    /// percent of the distance and sometimes get lost, sunlight
+    /// sometimes triggers infrared sensors.
+    pub fn default_noise(&self) -> SensorNoise {
+        match self {
+            ProximityModel::Ultrasonic(_) => SensorNoise::new(vec![
+                NoiseModel::Relative { std_dev: 0.012 },
+                NoiseModel::Dropout(0.01),
+            ]),
+            ProximityModel::Infrared(ir) => SensorNoise::new(vec![NoiseModel::Spike {
+                probability: 0.005,
+                magnitude: -ir.threshold,
+            }]),
+        }
+    }
+
+    /// What the real sensor outputs.
+    pub fn native_encoding(&self) -> SensorEncoding {
+        match self {
+            ProximityModel::Ultrasonic(_) => SensorEncoding::RawCm,
+            ProximityModel::Infrared(_) => SensorEncoding::Boolean,
+        }
+    }
+}
+
+/// How a sensor's read is written in the `ProxSensorReads` response
+/// (a single byte).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SensorEncoding {
+    /// Measured distance in cm, up to 255.
+    RawCm,
+    /// 1 if something was detected, 0 otherwise.
+    Boolean,
+    /// Measured distance, 0..255 over the sensor's max range.
+    ScaledByte,
+}
+
+impl SensorEncoding {
+    pub fn encode(&self, sensor: &ProximitySensor) -> u8 {
+        match self {
+            SensorEncoding::RawCm => (sensor.range * ProximitySensor::CM_PER_UNIT).min(255.0) as u8,
+            SensorEncoding::Boolean => sensor.detected as u8,
+            SensorEncoding::ScaledByte => {
+                (sensor.range / sensor.max_range() * 255.0).clamp(0.0, 255.0) as u8
+            }
+        }
+    }
+}
+
+/// What the `ProxSensorReads` response carries.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SensorReport {
+    /// Every sensor's read, encoded as its config says (like the robot's firmware).
+    #[default]
+    Firmware,
+    /// Every sensor's measured distance in cm, whatever its encoding.
+    RawDistance,
+}
+
+/// A proximity sensor's mount, and where its read goes in the
+/// `ProxSensorReads` response.
+#[derive(Clone, Debug)]
+pub struct SensorConfig {
+    pub name: &'static str,
+    /// Offset relative to Arenito, the sensor looks along its x axis.
+    pub offset: Transform,
+    pub model: ProximityModel,
+    /// Index within the response.
+    pub slot: usize,
+    pub encoding: SensorEncoding,
+    /// What the sensor can see.
+    pub layers: CollisionLayers,
+    pub noise: SensorNoise,
 }
 
-/// Proximity sensor, inspired by E18-D80NK.
+/// Proximity sensor, mounted on Arenito (see `SensorConfig`).
 ///
-/// It's initial `pos` and `rot` values are position and rotation offset
-/// relative to Arenito.
-/// Every frame its values must be computed relative to Arenito's current
-/// position. This is done using `ProximitySensor::get_orientation().`
+/// Sensors are read every frame (see `ProximitySensor::read`), the
+/// AI gets their reads with `ProximitySensor::report`.
 #[derive(Component)]
 pub struct ProximitySensor {
-    pub max_range: f32,
+    pub model: ProximityModel,
+    /// Last measured distance (max range if there's nothing there).
     pub range: f32,
-    pub index: usize,
+    /// Whether something is in range (an IR sensor's output).
+    pub detected: bool,
+    pub name: &'static str,
+    pub slot: usize,
+    pub encoding: SensorEncoding,
+    pub layers: CollisionLayers,
+    pub noise: SensorNoise,
 }
 
 impl ProximitySensor {
+    // sim units to cm
+    pub const CM_PER_UNIT: f32 = 33.0;
+
+    /// Sensor at slot 0, with its model's native encoding and noise.
+    pub fn new(model: ProximityModel) -> Self {
+        let mut sensor = ProximitySensor {
+            model,
+            range: 0.0,
+            detected: false,
+            name: "",
+            slot: 0,
+            encoding: model.native_encoding(),
+            layers: model.default_layers(),
+            noise: model.default_noise(),
+        };
+        sensor.reset();
+        sensor
+    }
+
+    pub fn from_config(config: &SensorConfig) -> Self {
+        ProximitySensor {
+            name: config.name,
+            slot: config.slot,
+            encoding: config.encoding,
+            layers: config.layers,
+            noise: config.noise.clone(),
+            ..Self::new(config.model)
+        }
+    }
+
+    pub fn with_noise(mut self, noise: SensorNoise) -> Self {
+        self.noise = noise;
+        self
+    }
+
+    /// Builds the `ProxSensorReads` response: one byte per slot, up to the
+    /// last slot taken (slots no sensor takes read 0).
+    pub fn report<'a>(
+        sensors: impl IntoIterator<Item = &'a ProximitySensor>,
+        mode: SensorReport,
+    ) -> Vec<u8> {
+        let mut reads = Vec::new();
+
+        for sensor in sensors {
+            if sensor.slot >= AISimMem::MAX_PROXIMITY_SENSOR_COUNT {
+                println!("Sensor {} is out of slots ({}).", sensor.name, sensor.slot);
+                continue;
+            }
+
+            let encoding = match mode {
+                SensorReport::Firmware => sensor.encoding,
+                SensorReport::RawDistance => SensorEncoding::RawCm,
+            };
+            if reads.len() <= sensor.slot {
+                reads.resize(sensor.slot + 1, 0);
+            }
+            reads[sensor.slot] = encoding.encode(sensor);
+        }
+
+        reads
+    }
+
+    /// Furthest distance the sensor can tell apart.
+    pub fn max_range(&self) -> f32 {
+        match self.model {
+            ProximityModel::Ultrasonic(us) => us.max_range,
+            ProximityModel::Infrared(ir) => ir.threshold + ir.hysteresis,
+        }
+    }
+
     /// Resets the sensor's current range.
     pub fn reset(&mut self) {
-        self.range = self.max_range;
+        self.range = self.max_range();
     }
 
     /// Draws the sensor's detection line.
-    pub fn draw_ray(&self, self_transform: &Transform, gizmos: &mut Gizmos) {
-        let color = if self.range == self.max_range {
-            Color::GREEN
-        } else {
+    pub fn draw_ray(&self, self_transform: &GlobalTransform, gizmos: &mut Gizmos) {
+        let color = if self.detected {
             Color::YELLOW
+        } else {
+            Color::GREEN
         };
 
         gizmos.ray(
-            self_transform.translation,
-            self_transform.rotation.mul_vec3(Vec3::X) * self.range,
+            self_transform.translation(),
+            Ray::from_transform(self_transform).dir * self.range,
             color,
         );
     }
 
-    pub fn set_index(mut self, index: usize) -> Self {
-        self.index = index;
-        self
+    /// Returns the rays the sensor casts: its axis first, then the
+    /// ultrasonic cone's edge.
+    fn rays(&self, self_transform: &GlobalTransform) -> Vec<Ray> {
+        let axis = Ray::from_transform(self_transform).with_max_distance(self.max_range());
+        let mut rays = vec![axis];
+
+        if let ProximityModel::Ultrasonic(us) = self.model {
+            let (sin, cos) = us.cone_angle.sin_cos();
+            rays.extend((0..us.rays).map(|i| {
+                let around = std::f32::consts::TAU * i as f32 / us.rays as f32;
+                let local = Vec3::new(cos, sin * around.cos(), sin * around.sin());
+                Ray::new(axis.org, self_transform.affine().transform_vector3(local))
+                    .with_max_distance(axis.max_dist)
+            }));
+        }
+
+        rays
     }
-}
 
-impl Default for ProximitySensor {
-    fn default() -> Self {
-        Self {
-            max_range: 3.0,
-            range: 3.0,
-            index: 0,
+    /// Reads the sensor against everything in its layers (the water's
+    /// surface included), updating its range and output.
+    /// Returns whether something was detected.
+    ///
+    /// Rays are cast in 3D, so sensors mounted high see over short things.
+    pub fn read(
+        &mut self,
+        self_transform: &GlobalTransform,
+        grid: &ObstacleGrid,
+        terrain: &Terrain,
+        rng: &mut impl Rng,
+    ) -> bool {
+        let sees_water = self.layers.intersects(CollisionLayers::WATER);
+        let distance = self
+            .rays(self_transform)
+            .iter()
+            .flat_map(|ray| {
+                let water = sees_water.then(|| terrain.water_hit(ray)).flatten();
+                let hit = grid.cast_layers(ray, self.layers).map(|hit| hit.distance);
+                [water, hit]
+            })
+            .flatten()
+            .fold(self.max_range(), f32::min);
+
+        self.measure(distance, rng)
+    }
+
+    /// Turns the real `distance` to the closest obstacle into what the
+    /// sensor reads (through its noise). Lost reads see nothing.
+    fn measure(&mut self, distance: f32, rng: &mut impl Rng) -> bool {
+        match self.model {
+            ProximityModel::Ultrasonic(us) => {
+                self.range = if distance >= us.max_range {
+                    us.max_range
+                } else {
+                    self.noise
+                        .apply(distance, rng)
+                        .map_or(us.max_range, |read| read.clamp(us.min_range, us.max_range))
+                };
+                self.detected = self.range < us.max_range;
+            }
+            ProximityModel::Infrared(ir) => {
+                let release = if self.detected {
+                    ir.threshold + ir.hysteresis
+                } else {
+                    ir.threshold
+                };
+                let read = self.noise.apply(distance, rng).unwrap_or(f32::INFINITY);
+                self.range = distance;
+                self.detected = read < release;
+            }
         }
+
+        self.detected
     }
 }
 
-impl RayCollider for ProximitySensor {
-    /// Checks collision with an object's mesh.
-    /// Sets self.range to the minimum range for this mehs.
-    fn collides_with_mesh(
-        &mut self,
-        self_transform: &Transform,
-        object_hull: &Vec<Triangle>,
-    ) -> bool {
-        let line = Line {
-            dir: self_transform.rotation.mul_vec3(Vec3::X),
-            org: self_transform.translation,
-        };
+#[cfg(test)]
+mod proximity_sensor_tests {
+    use super::*;
+    use crate::collision_shape::{Collider, CollisionShape};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    // a 2x2 wall facing -x at x = 1, centered at (y, z)
+    fn grid(y: f32, z: f32) -> ObstacleGrid {
+        let (a, b, c, d) = (
+            Vec3::new(1.0, y - 1.0, z - 1.0),
+            Vec3::new(1.0, y - 1.0, z + 1.0),
+            Vec3::new(1.0, y + 1.0, z + 1.0),
+            Vec3::new(1.0, y + 1.0, z - 1.0),
+        );
+        let hull = vec![Triangle { a, b, c }, Triangle { a, b: c, c: d }];
+        let mut grid = ObstacleGrid::default();
+        grid.rebuild([(
+            Entity::from_raw(0),
+            CollisionLayers::OBSTACLES,
+            hull.as_slice(),
+        )]);
+        grid
+    }
 
-        let mut dist = if self.range == 0.0 {
-            self.max_range
-        } else {
-            self.range
-        };
+    // no water around
+    fn sand() -> Terrain {
+        Terrain::new(crate::terrain::TerrainSource::Flat, 20.0, 20.0)
+    }
 
-        for face in object_hull {
-            match get_collision_point(line, *face) {
-                None => {}
-                Some(p) => {
-                    if point_inside_triangle(p, *face) {
-                        let d = self_transform.translation.distance(p);
-                        if d <= dist {
-                            dist = d;
-                        }
-                    }
-                }
-            };
+    // a standing can, 1 unit away along x
+    fn can_grid() -> ObstacleGrid {
+        let hull = Collider::new(CollisionShape::Cylinder {
+            radius: 0.15,
+            half_height: 0.235,
+        })
+        .world(&Transform::from_xyz(1.0, 0.235, 0.0).into())
+        .triangles();
+        let mut grid = ObstacleGrid::default();
+        grid.rebuild([(Entity::from_raw(0), CollisionLayers::CANS, hull.as_slice())]);
+        grid
+    }
+
+    #[test]
+    fn test_low_sensors_see_cans_high_sensors_see_over_them() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let low = GlobalTransform::from(Transform::from_xyz(0.0, 0.2, 0.0));
+        let high = GlobalTransform::from(Transform::from_xyz(0.0, 1.5, 0.0));
+
+        let mut sensor = ideal_infrared(2.0);
+        assert!(sensor.read(&low, &can_grid(), &sand(), &mut rng));
+        assert!((sensor.range - 0.85).abs() < 0.01);
+        assert!(!sensor.read(&high, &can_grid(), &sand(), &mut rng));
+    }
+
+    #[test]
+    fn test_layer_masks() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let low = GlobalTransform::from(Transform::from_xyz(0.0, 0.2, 0.0));
+        let mut sensor = ideal_infrared(2.0);
+        sensor.layers = CollisionLayers::OBSTACLES | CollisionLayers::DEPOSIT;
+
+        assert!(!sensor.read(&low, &can_grid(), &sand(), &mut rng));
+    }
+
+    #[test]
+    fn test_water_reflects_sound_not_infrared() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // past the sand's edge, looking down
+        let over_water = GlobalTransform::from(
+            Transform::from_xyz(10.5, 1.0, 0.0)
+                .with_rotation(Quat::from_rotation_z(-std::f32::consts::FRAC_PI_2)),
+        );
+        let grid = ObstacleGrid::default();
+
+        let mut ultrasonic = ideal_ultrasonic();
+        assert!(ultrasonic.read(&over_water, &grid, &sand(), &mut rng));
+        assert!((ultrasonic.range - 1.0).abs() < 0.001);
+
+        let mut infrared = ideal_infrared(2.0);
+        assert!(!infrared.read(&over_water, &grid, &sand(), &mut rng));
+    }
+
+    fn ideal_ultrasonic() -> ProximitySensor {
+        ProximitySensor::new(ProximityModel::Ultrasonic(Ultrasonic::default()))
+            .with_noise(SensorNoise::ideal())
+    }
+
+    fn ideal_infrared(threshold: f32) -> ProximitySensor {
+        ProximitySensor::new(ProximityModel::Infrared(Infrared::new(threshold)))
+            .with_noise(SensorNoise::ideal())
+    }
+
+    #[test]
+    fn test_ultrasonic_cone_sees_off_axis_obstacles() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // the wall's edge is 0.1 off the sensor's axis
+        let grid = grid(0.0, 1.1);
+        let t = GlobalTransform::IDENTITY;
+
+        let mut infrared = ideal_infrared(2.0);
+        assert!(!infrared.read(&t, &grid, &sand(), &mut rng));
+
+        let mut ultrasonic = ideal_ultrasonic();
+        assert!(ultrasonic.read(&t, &grid, &sand(), &mut rng));
+        assert!(ultrasonic.range > 1.0 && ultrasonic.range < 1.1);
+    }
+
+    #[test]
+    fn test_ultrasonic_dropouts_read_max_range() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut sensor =
+            ideal_ultrasonic().with_noise(SensorNoise::new(vec![NoiseModel::Dropout(1.0)]));
+
+        assert!(!sensor.read(
+            &GlobalTransform::IDENTITY,
+            &grid(0.0, 0.0),
+            &sand(),
+            &mut rng
+        ));
+        assert_eq!(sensor.range, 3.0);
+    }
+
+    #[test]
+    fn test_ultrasonic_clamps_to_min_range() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut sensor = ideal_ultrasonic();
+        sensor.measure(0.01, &mut rng);
+
+        assert_eq!(sensor.range, 0.06);
+    }
+
+    #[test]
+    fn test_infrared_hysteresis() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut sensor = ideal_infrared(1.0);
+
+        assert!(!sensor.measure(1.05, &mut rng));
+        assert!(sensor.measure(0.95, &mut rng));
+        // doesn't release until it's past threshold + hysteresis
+        assert!(sensor.measure(1.05, &mut rng));
+        assert!(!sensor.measure(1.15, &mut rng));
+    }
+
+    #[test]
+    fn test_infrared_sunlight_false_triggers() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut sensor = ProximitySensor::new(ProximityModel::Infrared(Infrared::new(1.0)));
+        sensor.noise.models = vec![NoiseModel::Spike {
+            probability: 1.0,
+            magnitude: -1.0,
+        }];
+
+        // whatever's in range
+        assert!(sensor.measure(1.1, &mut rng));
+        // lost reads see nothing
+        sensor.noise = SensorNoise::new(vec![NoiseModel::Dropout(1.0)]);
+        assert!(!sensor.measure(0.5, &mut rng));
+    }
+
+    #[test]
+    fn test_ultrasonic_noise_is_relative() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut sensor = ideal_ultrasonic().with_noise(SensorNoise::new(vec![
+            NoiseModel::Relative { std_dev: 0.01 },
+            NoiseModel::Quantization(1.0 / ProximitySensor::CM_PER_UNIT),
+        ]));
+
+        for _ in 0..100 {
+            sensor.measure(2.0, &mut rng);
+            assert!((sensor.range - 2.0).abs() < 0.1);
+            // whole cm
+            let cm = sensor.range * ProximitySensor::CM_PER_UNIT;
+            assert!((cm - cm.round()).abs() < 0.001);
         }
+    }
+
+    fn at_slot(model: ProximityModel, slot: usize, range: f32) -> ProximitySensor {
+        let mut sensor = ProximitySensor::from_config(&SensorConfig {
+            name: "test",
+            offset: Transform::IDENTITY,
+            model,
+            slot,
+            encoding: model.native_encoding(),
+            layers: model.default_layers(),
+            noise: SensorNoise::ideal(),
+        });
+        sensor.measure(range, &mut StdRng::seed_from_u64(0));
+        sensor
+    }
+
+    #[test]
+    fn test_report_follows_slots_and_encodings() {
+        let sensors = [
+            at_slot(ideal_infrared(1.0).model, 3, 0.5),
+            at_slot(ideal_ultrasonic().model, 0, 1.5),
+        ];
+
+        assert_eq!(
+            ProximitySensor::report(&sensors, SensorReport::Firmware),
+            vec![49, 0, 0, 1]
+        );
+        assert_eq!(
+            ProximitySensor::report(&sensors, SensorReport::RawDistance),
+            vec![49, 0, 0, 16]
+        );
+    }
+
+    #[test]
+    fn test_scaled_byte_encoding() {
+        let sensor = at_slot(ideal_ultrasonic().model, 0, 1.5);
 
-        self.range = dist;
+        assert_eq!(SensorEncoding::ScaledByte.encode(&sensor), 127);
+        assert_eq!(SensorEncoding::Boolean.encode(&sensor), 1);
+    }
 
-        dist < self.max_range
+    #[test]
+    fn test_report_skips_sensors_out_of_slots() {
+        let sensors = [at_slot(ideal_ultrasonic().model, 40, 1.0)];
+
+        assert!(ProximitySensor::report(&sensors, SensorReport::Firmware).is_empty());
     }
 }
 
@@ -424,8 +1043,9 @@ mod sensor_read_tests {
 
     #[test]
     fn sensor_acc_reads_dont_go_to_negative_values() {
-        let mut rng = thread_rng();
+        let mut rng = rand::thread_rng();
         let mut arenito = Arenito::new(&ArenitoConfig::default());
+        let mut imu = MPU6050::default();
 
         for _ in 0..100 {
             arenito.acc = Vec3::new(
@@ -433,12 +1053,22 @@ mod sensor_read_tests {
                 rng.gen_range(-2.1..2.1),
                 rng.gen_range(-2.1..2.1),
             );
-            let read = MPU6050::read_acc(&arenito);
+            let read = imu.read_acc(&arenito, &mut rng);
             acc_within_value(&read);
         }
     }
 
-    // No idea how to or what to test for gyro reads...
+    #[test]
+    fn ideal_imu_reads_real_rotation() {
+        let mut rng = rand::thread_rng();
+        let mut imu = MPU6050::ideal();
+        let transform = Transform::from_rotation(Quat::from_rotation_y(0.3));
+
+        assert_eq!(
+            imu.read_rot(&transform, &mut rng),
+            transform.rotation.mul_vec3(Vec3::X)
+        );
+    }
 }
 
 #[cfg(test)]
@@ -479,7 +1109,21 @@ mod ai_sim_mem_tests {
         let mut buf: Vec<u8> = vec![AISimMem::AI_FRONT_CAM_REQUEST, 0];
         let aisim = AISimMem::from_buf(&mut buf);
 
-        assert_eq!(Some(SimInstruction::FrontCamFrame), aisim.get_instruction());
+        assert_eq!(
+            Some(SimInstruction::CameraFrame(0)),
+            aisim.get_instruction()
+        );
+    }
+
+    #[test]
+    fn test_get_instruction_camera_frame_by_id() {
+        let mut buf: Vec<u8> = vec![AISimMem::AI_CAMERA_FRAME_REQUEST, 3];
+        let aisim = AISimMem::from_buf(&mut buf);
+
+        assert_eq!(
+            Some(SimInstruction::CameraFrame(3)),
+            aisim.get_instruction()
+        );
     }
 
     #[test]
@@ -530,6 +1174,195 @@ mod ai_sim_mem_tests {
         assert_eq!(None, aisim.get_instruction());
     }
 
+    #[test]
+    fn test_get_instruction_hopper_count() {
+        let mut buf: Vec<u8> = vec![AISimMem::AI_HOPPER_COUNT_REQUEST, 0];
+        let aisim = AISimMem::from_buf(&mut buf);
+
+        assert_eq!(Some(SimInstruction::HopperCount), aisim.get_instruction());
+    }
+
+    #[test]
+    fn test_export_hopper_count() {
+        let mut buf: Vec<u8> = vec![AISimMem::AI_HOPPER_COUNT_REQUEST, 0];
+        let mut aisim = AISimMem::from_buf(&mut buf);
+
+        aisim.export_hopper_count(5);
+        assert_eq!(buf, vec![AISimMem::SIM_AKNOWLEDGE_INSTRUCTION, 5]);
+    }
+
+    #[test]
+    fn test_get_instruction_extend_backdoor() {
+        let mut buf: Vec<u8> = vec![AISimMem::AI_MOVE_INSTRUCTION, AISimMem::EXTEND_BACKDOOR];
+        let aisim = AISimMem::from_buf(&mut buf);
+
+        assert_eq!(
+            Some(SimInstruction::ExtendBackdoor),
+            aisim.get_instruction()
+        );
+    }
+
+    #[test]
+    fn test_export_backdoor_switches() {
+        let mut buf: Vec<u8> = vec![AISimMem::AI_BACKDOOR_SWITCHES_REQUEST, 0, 0];
+        let mut aisim = AISimMem::from_buf(&mut buf);
+
+        assert_eq!(
+            Some(SimInstruction::BackdoorSwitches),
+            aisim.get_instruction()
+        );
+        aisim.export_backdoor_switches((false, true));
+        assert_eq!(buf, vec![AISimMem::SIM_AKNOWLEDGE_INSTRUCTION, 0, 1]);
+    }
+
+    #[test]
+    fn test_get_instruction_respawn_cans() {
+        let mut buf: Vec<u8> = vec![AISimMem::AI_RESPAWN_CANS, 1, 0, 0];
+        let aisim = AISimMem::from_buf(&mut buf);
+
+        assert_eq!(Some(SimInstruction::RespawnCans), aisim.get_instruction());
+    }
+
+    #[test]
+    fn test_get_instruction_set_respawn_policy() {
+        let mut buf: Vec<u8> = vec![AISimMem::AI_RESPAWN_CANS, 3, 1, 30];
+        let aisim = AISimMem::from_buf(&mut buf);
+
+        assert_eq!(
+            Some(SimInstruction::SetRespawnPolicy(RespawnPolicy {
+                trigger: RespawnTrigger::Periodic(30.0),
+                placement: RespawnPlacement::Random,
+            })),
+            aisim.get_instruction()
+        );
+    }
+
+    #[test]
+    fn test_get_instruction_respawn_period_is_at_least_a_second() {
+        let mut buf: Vec<u8> = vec![AISimMem::AI_RESPAWN_CANS, 3, 0, 0];
+        let aisim = AISimMem::from_buf(&mut buf);
+
+        assert_eq!(
+            Some(SimInstruction::SetRespawnPolicy(RespawnPolicy {
+                trigger: RespawnTrigger::Periodic(1.0),
+                placement: RespawnPlacement::Original,
+            })),
+            aisim.get_instruction()
+        );
+    }
+
+    #[test]
+    fn test_get_instruction_respawn_other_value_is_ignored() {
+        let mut trigger: Vec<u8> = vec![AISimMem::AI_RESPAWN_CANS, 7, 0, 0];
+        let mut placement: Vec<u8> = vec![AISimMem::AI_RESPAWN_CANS, 2, 5, 0];
+
+        assert_eq!(
+            Some(SimInstruction::Ignored),
+            AISimMem::from_buf(&mut trigger).get_instruction()
+        );
+        assert_eq!(
+            Some(SimInstruction::Ignored),
+            AISimMem::from_buf(&mut placement).get_instruction()
+        );
+    }
+
+    #[test]
+    fn test_export_camera_geometry() {
+        let mut buf: Vec<u8> = vec![0; 1 + 25 * 4];
+        buf[0] = AISimMem::AI_CAMERA_GEOMETRY_REQUEST;
+        let mut aisim = AISimMem::from_buf(&mut buf);
+        assert_eq!(
+            Some(SimInstruction::CameraGeometry(0)),
+            aisim.get_instruction()
+        );
+
+        let geometry = CameraGeometry::new(
+            Vec4::new(50.0, 50.0, 50.0, 50.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Quat::from_rotation_z(-std::f32::consts::FRAC_PI_4),
+            0.0,
+        );
+        aisim.export_camera_geometry(&geometry);
+
+        let values: Vec<f32> = buf[1..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(buf[0], AISimMem::SIM_AKNOWLEDGE_INSTRUCTION);
+        assert_eq!(values[..4], [50.0, 50.0, 50.0, 50.0]);
+        assert_eq!(values[5], ProximitySensor::CM_PER_UNIT);
+
+        // center pixel sees the ground 1 unit ahead
+        let h = Mat3::from_cols_slice(&values[16..]).transpose();
+        let ground = h * Vec3::new(50.0, 50.0, 1.0);
+        assert!((ground.x / ground.z - ProximitySensor::CM_PER_UNIT).abs() < 1e-3);
+        assert!((ground.y / ground.z).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_get_instruction_camera_mount() {
+        // camera 1, pitch -45°, yaw 12.5°
+        let mut buf: Vec<u8> = vec![AISimMem::AI_CAMERA_MOUNT, 1, 0x3e, 0xfe, 0x7d, 0x00];
+        let aisim = AISimMem::from_buf(&mut buf);
+
+        let Some(SimInstruction::PointCamera { camera, pitch, yaw }) = aisim.get_instruction()
+        else {
+            panic!("expected a camera mount instruction");
+        };
+        assert_eq!(camera, 1);
+        assert!((pitch - (-45.0_f32).to_radians()).abs() < 1e-6);
+        assert!((yaw - 12.5_f32.to_radians()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_get_instruction_camera_settings() {
+        let mut buf: Vec<u8> = vec![
+            AISimMem::AI_CAMERA_SETTINGS,
+            1,
+            0x2c,
+            0x01,
+            32,
+            128,
+            128,
+            64,
+        ];
+        let aisim = AISimMem::from_buf(&mut buf);
+
+        assert_eq!(
+            Some(SimInstruction::SetCameraSettings(
+                1,
+                CameraSettings {
+                    exposure: Some(0.03),
+                    gain: 2.0,
+                    white_balance: Vec3::new(1.0, 1.0, 0.5),
+                }
+            )),
+            aisim.get_instruction()
+        );
+
+        // auto exposure
+        buf[2] = 0;
+        buf[3] = 0;
+        let aisim = AISimMem::from_buf(&mut buf);
+        assert!(matches!(
+            aisim.get_instruction(),
+            Some(SimInstruction::SetCameraSettings(
+                1,
+                CameraSettings { exposure: None, .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_hold_instruction() {
+        let mut buf: Vec<u8> = vec![AISimMem::AI_DUMP_CANS, 3];
+        let mut aisim = AISimMem::from_buf(&mut buf);
+
+        aisim.hold_instruction();
+        assert_eq!(None, aisim.get_instruction());
+        assert_eq!(buf[0], AISimMem::SIM_SCAN_WAIT);
+    }
+
     #[test]
     fn test_confirm_instruction() {
         let mut buf: Vec<u8> = vec![100, 101, 102, 103];
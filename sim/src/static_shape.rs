@@ -1,8 +1,7 @@
-use bevy::{
-    prelude::*,
-    render::{camera::RenderTarget, render_resource::*},
-    window::{Window, WindowRef, WindowResolution},
+use crate::{
+    camera_target::CameraTarget, frame::CameraSettings, mount::CameraMount, terrain::Terrain,
 };
+use bevy::{prelude::*, render::render_resource::*};
 
 const IMG_WIDTH: f32 = 512.0;
 const IMG_HEIGHT: f32 = 512.0;
@@ -115,15 +114,91 @@ impl From<CameraPrism> for Mesh {
     }
 }
 
+/// Camera's intrinsics, extrinsics and pixel to ground homography,
+/// for the AI to turn what it sees into distances.
+///
+/// Follows OpenCV's conventions: the camera looks along its z axis, with
+/// x to the right and y down. Positions are relative to Arenito (x forward,
+/// y up, z to the right).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraGeometry {
+    /// fx, fy, cx, cy, in pixels.
+    pub intrinsics: Vec4,
+    /// Camera's position.
+    pub position: Vec3,
+    /// Camera to Arenito rotation.
+    pub rotation: Mat3,
+    /// Maps a pixel (u, v, 1) to a ground point (x, z, w), the pixel sees
+    /// the ground at (x / w, z / w) when w is positive (over the horizon otherwise).
+    pub homography: Mat3,
+}
+
+impl CameraGeometry {
+    /// `view` is the camera's rotation (looking along x, see `CameraMount::rotation`),
+    /// `ground` the ground's height.
+    pub fn new(intrinsics: Vec4, position: Vec3, view: Quat, ground: f32) -> Self {
+        let [fx, fy, cx, cy] = intrinsics.to_array();
+        let rotation = Mat3::from_cols(
+            view.mul_vec3(Vec3::Z),
+            view.mul_vec3(Vec3::NEG_Y),
+            view.mul_vec3(Vec3::X),
+        );
+        let k_inv = Mat3::from_cols(
+            Vec3::new(1.0 / fx, 0.0, 0.0),
+            Vec3::new(0.0, 1.0 / fy, 0.0),
+            Vec3::new(-cx / fx, -cy / fy, 1.0),
+        );
+
+        // a pixel's ray is `position + s * dir`, with dir = m * (u, v, 1),
+        // it hits the ground at s = (ground - position.y) / dir.y
+        let m = rotation * k_inv;
+        let (mx, my, mz) = (m.row(0), m.row(1), m.row(2));
+        let depth = ground - position.y;
+        let homography = Mat3::from_cols(
+            (position.x * my + depth * mx) / depth,
+            (position.z * my + depth * mz) / depth,
+            my / depth,
+        )
+        .transpose();
+
+        CameraGeometry {
+            intrinsics,
+            position,
+            rotation,
+            homography,
+        }
+    }
+
+    /// Returns where pixel (u, v) sees the ground (x, z), None if it doesn't.
+    pub fn pixel_to_ground(&self, pixel: Vec2) -> Option<Vec2> {
+        let p = self.homography * pixel.extend(1.0);
+        (p.z > 0.0).then(|| p.truncate() / p.z)
+    }
+}
+
+/// Marks a camera's model, by the camera's id.
+#[derive(Component)]
+pub struct CameraModel(pub usize);
+
 /// Visualization of the area visible by Arneito's camera.
 #[derive(Component, Clone)]
 pub struct CameraData {
+    // Shown in the camera's preview window
+    pub name: &'static str,
+    // Render size, in pixels (exported frames are resized, see `AISimMem::FRAME_SIZE`)
+    pub resolution: UVec2,
     // Horizontal view angle, in degrees
     pub ha: f32,
     // Vertical view angle, in degrees
     pub va: f32,
-    // Camera's z rotation
-    pub offset: Transform,
+    // Mount's position, relative to Arenito
+    pub offset: Vec3,
+    // Pan/tilt mount, where the camera's pointing
+    pub mount: CameraMount,
+    // Exposure, gain and white balance, set by the AI
+    pub settings: CameraSettings,
+    // Sunlight shining into the lens (see `Capture::glare`)
+    pub glare: f32,
     // Computed:
     // Edges of visible area
     pub points: Vec<Vec3>,
@@ -135,12 +210,17 @@ pub struct CameraData {
 }
 
 impl CameraData {
-    pub fn new(ha: f32, va: f32, offset: Transform) -> Self {
+    pub fn new(ha: f32, va: f32, offset: Vec3, mount: CameraMount) -> Self {
         Self {
+            name: "",
+            resolution: UVec2::new(IMG_WIDTH as u32, IMG_HEIGHT as u32),
             ha: ha.to_radians(),
             va: va.to_radians(),
             points: Vec::new(),
             offset,
+            mount,
+            settings: CameraSettings::default(),
+            glare: 0.0,
             long_side: 0.0,
             short_side: 0.0,
             height: 0.0,
@@ -148,9 +228,23 @@ impl CameraData {
         }
     }
 
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn with_resolution(mut self, width: u32, height: u32) -> Self {
+        self.resolution = UVec2::new(width, height);
+        self
+    }
+
     /// Calculates the points (edges) that limit the camera's visible area,
     /// as well as the size of the trapeze.
-    pub fn compute_area(&mut self, arenito_y: f32) {
+    ///
+    /// The area is found by casting a ray from the camera through each corner
+    /// of its view prism until it reaches the ground. The points are stored
+    /// relative to Arenito (`arenito_transform`).
+    pub fn compute_area(&mut self, arenito_transform: &Transform, terrain: &Terrain) {
         // A and B are the closest points to the camera
         // in right-to-left order.
         // C and D are in left-to-right order, further away.
@@ -161,42 +255,24 @@ impl CameraData {
         //
         //        cam
 
-        let cam_pos = self.offset.translation + Vec3::Y * (arenito_y - 0.01);
-        let euler = self.offset.rotation.to_euler(EulerRot::XYZ);
-
-        let q = Quat::from_euler(EulerRot::XYZ, euler.1, 0.0, euler.0);
-        let mut points = CameraPrism::from_cam(&self).get_points();
-
-        for i in 0..points.len() {
-            // rotate each point and move to correct position
-            let p = q.mul_vec3(points[i]) + cam_pos;
-
-            // since it's about a 3d line, we have to consider two planes xy and xz.
-            //
-            // starting with xy: the line equation goes: y - y_0 = m(x - x_0)
-            // we know that y = 0, because we want to know where it reaches the ground:
-            // (0) - y_0 = m(x - x_0)
-            // -y_0 = mx - mx_0
-            //
-            // and we want to find x:
-            // mx = mx_0 - y_0
-            // x = x_0 - (y_0 / m)
-            //
-            // we also know that the initial position (x_0 and y_0) is the camera's,
-            // so, we can re-write the equation as:
-            // x = pos.x - (pos.y / m)
-            //
-            // now the xz plane: based on the same equation, but replacing y by z,
-            // this plane's line equation is: z - z_0 = m(x - x_0)
-            // since we already know x, we just have to calculate this plane's slope
-            // and substitute the rest:
-            // z = m(x - x_0) + z_0
-
-            let mxy = (p.y - cam_pos.y) / (p.x - cam_pos.x); // xy slope
-            let mxz = (p.z - cam_pos.z) / (p.x - cam_pos.x); // xz slope
-
-            let x = cam_pos.x - (cam_pos.y / mxy);
-            points[i] = Vec3::new(x, 0.015, mxz * (x - cam_pos.x) + cam_pos.z);
+        // rays that never reach the ground (pointing over the horizon)
+        // are cut at this distance
+        const MAX_VIEW_DISTANCE: f32 = 20.0;
+        // lift the area a bit, so that it's drawn over the sand
+        const GROUND_OFFSET: Vec3 = Vec3::new(0.0, 0.015, 0.0);
+
+        let cam_pos = arenito_transform.transform_point(self.offset);
+        let q = self.view_rotation(arenito_transform);
+        let to_local = arenito_transform.compute_matrix().inverse();
+        let mut points = CameraPrism::from_cam(self).get_points();
+
+        for point in points.iter_mut() {
+            let dir = q.mul_vec3(*point);
+            let hit = terrain
+                .raycast(cam_pos, dir)
+                .unwrap_or(cam_pos + dir.normalize() * MAX_VIEW_DISTANCE);
+
+            *point = to_local.transform_point3(hit + GROUND_OFFSET);
         }
 
         self.points = points;
@@ -213,48 +289,94 @@ impl CameraData {
         );
     }
 
+    /// Camera's rotation, given Arenito's transform: it looks along its x axis.
+    fn view_rotation(&self, arenito_transform: &Transform) -> Quat {
+        arenito_transform.rotation * self.mount.rotation()
+    }
+
+    /// Camera model's transform, relative to Arenito.
+    pub fn model_transform(&self) -> Transform {
+        Transform::from_translation(self.offset).with_rotation(self.mount.rotation())
+    }
+
+    /// Camera's transform, relative to Arenito (bevy's cameras look along -z).
+    pub fn camera_transform(&self) -> Transform {
+        let rotation = self.mount.rotation();
+        Transform::from_translation(self.offset)
+            .looking_to(rotation.mul_vec3(Vec3::X), rotation.mul_vec3(Vec3::Y))
+    }
+
+    /// Direction the camera looks at, given Arenito's transform.
+    pub fn view_dir(&self, arenito_transform: &Transform) -> Vec3 {
+        self.view_rotation(arenito_transform).mul_vec3(Vec3::X)
+    }
+
+    /// Returns how the camera projects the ground onto a `size` x `size` frame,
+    /// the ground is taken as the plane under Arenito.
+    pub fn geometry(
+        &self,
+        arenito_transform: &Transform,
+        terrain: &Terrain,
+        size: f32,
+    ) -> CameraGeometry {
+        let (x, z) = (
+            arenito_transform.translation.x,
+            arenito_transform.translation.z,
+        );
+        let to_local = arenito_transform.compute_matrix().inverse();
+        let ground = to_local
+            .transform_point3(Vec3::new(x, terrain.height_at(x, z), z))
+            .y;
+
+        CameraGeometry::new(
+            Vec4::new(
+                self.focal_length(size),
+                size / 2.0 / (self.va / 2.0).tan(),
+                size / 2.0,
+                size / 2.0,
+            ),
+            self.offset,
+            self.mount.rotation(),
+            ground,
+        )
+    }
+
+    /// Focal length, in pixels of a `width` pixels wide frame.
+    pub fn focal_length(&self, width: f32) -> f32 {
+        width / 2.0 / (self.ha / 2.0).tan()
+    }
+
     pub fn front() -> Self {
         Self::new(
             45.0,
             45.0,
-            Transform::from_xyz(0.75, 1.3, 0.0).with_rotation(Quat::from_euler(
-                EulerRot::XYZ,
-                (-40.0_f32).to_radians(),
-                0.0,
-                0.0,
-            )),
+            Vec3::new(0.75, 1.3, 0.0),
+            CameraMount::new(0.0, (-40.0_f32).to_radians()),
         )
+        .with_name("Front view")
     }
 
     pub fn rear() -> Self {
         Self::new(
             45.0,
             45.0,
-            Transform::from_xyz(-0.75, 0.7, 0.0).with_rotation(Quat::from_euler(
-                EulerRot::XYZ,
-                (40.0_f32).to_radians(),
-                std::f32::consts::PI,
-                0.0,
-            )),
+            Vec3::new(-0.75, 0.7, 0.0),
+            CameraMount::new(std::f32::consts::PI, (-40.0_f32).to_radians()),
         )
+        .with_name("Rear view")
     }
 
-    fn get_window(title: String, visible: bool) -> Window {
-        Window {
-            title,
-            visible,
-            resolution: WindowResolution::new(IMG_WIDTH, IMG_HEIGHT),
-            resizable: false,
-            ..default()
-        }
-    }
-
-    fn get_camera_bundle(&self, window: Entity, transform: Transform) -> Camera3dBundle {
+    fn get_camera_bundle(&self, target: &CameraTarget, transform: Transform) -> Camera3dBundle {
         Camera3dBundle {
             camera: Camera {
-                target: RenderTarget::Window(WindowRef::Entity(window)),
+                target: target.render_target(),
                 ..default()
             },
+            projection: PerspectiveProjection {
+                fov: self.va,
+                ..default()
+            }
+            .into(),
             transform,
             ..default()
         }
@@ -265,28 +387,83 @@ impl CameraData {
         parent: &mut ChildBuilder<'_, '_, '_>,
         materials: &mut ResMut<Assets<StandardMaterial>>,
         asset_server: &Res<AssetServer>,
-        component: &(impl Component + Copy),
-        title: String,
-        visible: bool,
+        images: &mut Assets<Image>,
+        id: usize,
+        preview: bool,
     ) {
-        let euler = self.offset.rotation.to_euler(EulerRot::XYZ);
-        let model_transform = Transform::from_translation(self.offset.translation)
-            .with_rotation(Quat::from_euler(EulerRot::XYZ, euler.1, 0.0, euler.0));
-
-        parent.spawn(PbrBundle {
-            mesh: asset_server.load("models/camara.obj"),
-            material: materials.add(Color::BLACK.into()),
-            transform: model_transform,
-            ..default()
-        });
+        parent.spawn((
+            PbrBundle {
+                mesh: asset_server.load("models/camara.obj"),
+                material: materials.add(Color::BLACK.into()),
+                transform: self.model_transform(),
+                ..default()
+            },
+            CameraModel(id),
+        ));
+
+        let target = CameraTarget {
+            id,
+            image: images.add(CameraTarget::image(self.resolution)),
+            preview,
+        };
+
+        parent.spawn((
+            self.get_camera_bundle(&target, self.camera_transform()),
+            target,
+            Name::new(self.name),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod camera_geometry_tests {
+    use super::*;
+
+    fn geometry(pitch: f32) -> CameraGeometry {
+        // 90° view angles, 100x100 frame: f = 50 px
+        CameraGeometry::new(
+            Vec4::new(50.0, 50.0, 50.0, 50.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Quat::from_rotation_z(pitch.to_radians()),
+            0.0,
+        )
+    }
+
+    #[test]
+    fn test_center_pixel_sees_where_camera_looks() {
+        let ground = geometry(-45.0)
+            .pixel_to_ground(Vec2::new(50.0, 50.0))
+            .unwrap();
+
+        assert!(ground.abs_diff_eq(Vec2::new(1.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn test_pixels_to_the_right_see_ground_to_the_right() {
+        let ground = geometry(-45.0)
+            .pixel_to_ground(Vec2::new(100.0, 50.0))
+            .unwrap();
+
+        // same row, same distance ahead
+        assert!((ground.x - 1.0).abs() < 1e-5);
+        assert!(ground.y > 0.0);
+    }
+
+    #[test]
+    fn test_pixels_over_the_horizon_see_no_ground() {
+        let geometry = geometry(-30.0);
+
+        assert!(geometry.pixel_to_ground(Vec2::new(50.0, 0.0)).is_none());
+        assert!(geometry.pixel_to_ground(Vec2::new(50.0, 100.0)).is_some());
+    }
 
-        let mut cam_transform =
-            Transform::from_translation(self.offset.translation).looking_to(Vec3::X, Vec3::Y);
-        cam_transform.rotation *= self.offset.rotation;
-        let window = parent
-            .spawn((Self::get_window(title, visible), *component))
-            .id();
+    #[test]
+    fn test_rotation_follows_opencv_axes() {
+        let geometry = geometry(-90.0);
 
-        parent.spawn(self.get_camera_bundle(window, cam_transform));
+        // looking down, image's top is forward
+        assert!((geometry.rotation * Vec3::Z).abs_diff_eq(Vec3::NEG_Y, 1e-6));
+        assert!((geometry.rotation * Vec3::NEG_Y).abs_diff_eq(Vec3::X, 1e-6));
+        assert!((geometry.rotation * Vec3::X).abs_diff_eq(Vec3::Z, 1e-6));
     }
 }
@@ -0,0 +1,120 @@
+use crate::arenito::ShorelineEvent;
+use bevy::prelude::*;
+use std::fs;
+
+/// A plugin for keeping track of how well Arenito is doing.
+///
+/// This plugin adds:
+/// - EpisodeStats resource
+/// - Stats HUD
+/// - Stats file exporter
+pub struct EpisodeStatsPlugin;
+
+impl Plugin for EpisodeStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EpisodeStats::default())
+            .add_systems(Startup, spawn_stats_text)
+            .add_systems(
+                Update,
+                (
+                    count_shoreline_events,
+                    update_stats_text,
+                    export_episode_stats,
+                ),
+            );
+    }
+}
+
+/// Everything that happened during the current episode (since the
+/// simulation started).
+#[derive(Resource, Default, Debug)]
+pub struct EpisodeStats {
+    /// Cans Arenito picked up.
+    pub collected_cans: u32,
+    /// Things that weren't cans, that Arenito picked up anyway.
+    pub distractor_pickups: u32,
+    /// Cans that landed inside the deposit.
+    pub deposited_cans: u32,
+    /// Cans that were dumped out of the deposit.
+    pub littered_cans: u32,
+    /// Cans that ended up in the water.
+    pub lost_cans: u32,
+    /// How many times Arenito went into the water.
+    pub water_entries: u32,
+}
+
+impl EpisodeStats {
+    pub const FILENAME: &'static str = "episode_stats.txt";
+
+    /// Returns the stats as `key=value` lines.
+    pub fn to_text(&self) -> String {
+        format!(
+            "collected_cans={}\ndistractor_pickups={}\ndeposited_cans={}\nlittered_cans={}\nlost_cans={}\nwater_entries={}\n",
+            self.collected_cans,
+            self.distractor_pickups,
+            self.deposited_cans,
+            self.littered_cans,
+            self.lost_cans,
+            self.water_entries
+        )
+    }
+}
+
+#[derive(Component)]
+struct StatsText;
+
+fn spawn_stats_text(mut commands: Commands) {
+    let style = TextStyle {
+        font_size: 20.0,
+        ..default()
+    };
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(" Deposited: ", style.clone()),
+            TextSection::new("0", style.clone()),
+            TextSection::new(" Litter: ", style.clone()),
+            TextSection::new("0", style.clone()),
+            TextSection::new(" Distractors: ", style.clone()),
+            TextSection::new("0", style),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(22.0),
+            ..default()
+        }),
+        StatsText,
+    ));
+}
+
+fn count_shoreline_events(
+    mut events: EventReader<ShorelineEvent>,
+    mut stats: ResMut<EpisodeStats>,
+) {
+    for event in events.read() {
+        if *event == ShorelineEvent::EnteredWater {
+            stats.water_entries += 1;
+        }
+    }
+}
+
+fn update_stats_text(stats: Res<EpisodeStats>, mut text: Query<&mut Text, With<StatsText>>) {
+    if !stats.is_changed() {
+        return;
+    }
+
+    let mut text = text.single_mut();
+    text.sections[1].value = stats.deposited_cans.to_string();
+    text.sections[3].value = stats.littered_cans.to_string();
+    text.sections[5].value = stats.distractor_pickups.to_string();
+}
+
+/// Writes the stats to `EpisodeStats::FILENAME` every time they change.
+fn export_episode_stats(stats: Res<EpisodeStats>) {
+    if !stats.is_changed() {
+        return;
+    }
+
+    if fs::write(EpisodeStats::FILENAME, stats.to_text()).is_err() {
+        println!("Could not export episode stats!");
+    }
+}
@@ -0,0 +1,801 @@
+use crate::collision::{Ray, *};
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+
+/// Where the sand's heights come from.
+#[derive(Copy, Clone)]
+pub enum TerrainSource {
+    /// Completely flat sand, like the old `shape::Plane`.
+    Flat,
+    /// Grayscale image (relative to `assets/`), where white maps to `max_height`.
+    Heightmap(&'static str, f32),
+    /// Procedural value noise.
+    Noise {
+        seed: u32,
+        amplitude: f32,
+        frequency: f32,
+    },
+}
+
+/// What the ground is made of.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GroundMaterial {
+    DrySand,
+    WetSand,
+    PackedSand,
+    Pebbles,
+}
+
+impl GroundMaterial {
+    /// Friction needed for the wheels not to slip at all.
+    const REQUIRED_FRICTION: f32 = 0.5;
+
+    /// Friction coefficient between the wheels and the ground.
+    pub fn friction(&self) -> f32 {
+        match self {
+            GroundMaterial::DrySand => 0.6,
+            GroundMaterial::WetSand => 0.35,
+            GroundMaterial::PackedSand => 0.7,
+            GroundMaterial::Pebbles => 0.45,
+        }
+    }
+
+    /// Rolling resistance coefficient.
+    pub fn rolling_resistance(&self) -> f32 {
+        match self {
+            GroundMaterial::DrySand => 0.15,
+            GroundMaterial::WetSand => 0.1,
+            GroundMaterial::PackedSand => 0.05,
+            GroundMaterial::Pebbles => 0.25,
+        }
+    }
+
+    /// Fraction of a wheel's speed that actually moves Arenito.
+    ///
+    /// Arenito's `velocity_k` was tuned on dry sand, so dry sand's
+    /// traction is 1. Wheels slip when there's less friction than
+    /// `REQUIRED_FRICTION`, and rolling resistance slows them down.
+    pub fn traction(&self) -> f32 {
+        let grip = (self.friction() / Self::REQUIRED_FRICTION).min(1.0);
+        grip * (1.0 - self.rolling_resistance())
+            / (1.0 - GroundMaterial::DrySand.rolling_resistance())
+    }
+
+    /// Color the sand's texture is tinted with.
+    pub fn tint(&self) -> Color {
+        match self {
+            GroundMaterial::DrySand => Color::WHITE,
+            GroundMaterial::WetSand => Color::rgb(0.62, 0.58, 0.52),
+            GroundMaterial::PackedSand => Color::rgb(0.9, 0.87, 0.82),
+            GroundMaterial::Pebbles => Color::rgb(0.55, 0.55, 0.55),
+        }
+    }
+}
+
+/// A circular patch of ground made of `material`.
+#[derive(Copy, Clone)]
+pub struct MaterialZone {
+    pub material: GroundMaterial,
+    // (x, z)
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+impl MaterialZone {
+    pub fn new(material: GroundMaterial, x: f32, z: f32, radius: f32) -> Self {
+        MaterialZone {
+            material,
+            center: Vec2::new(x, z),
+            radius,
+        }
+    }
+
+    /// Checks whether the point (x, z) is inside the zone.
+    pub fn contains(&self, x: f32, z: f32) -> bool {
+        self.center.distance(Vec2::new(x, z)) <= self.radius
+    }
+}
+
+/// The boundary between sand and water.
+///
+/// The sand's edge is pushed in and out by noise (`irregularity`), so it isn't
+/// a straight line. Right next to the water there's a band of wet sand
+/// (`wet_band` wide), and past the edge, the ground keeps going down under
+/// shallow water for `shallow_width` units.
+#[derive(Copy, Clone)]
+pub struct Shoreline {
+    pub seed: u32,
+    pub irregularity: f32,
+    pub wet_band: f32,
+    pub shallow_width: f32,
+}
+
+impl Shoreline {
+    /// How fast the ground goes down under water.
+    const SHALLOW_SLOPE: f32 = 0.15;
+    const NOISE_FREQUENCY: f32 = 0.3;
+}
+
+impl Default for Shoreline {
+    fn default() -> Self {
+        Shoreline {
+            seed: 28,
+            irregularity: 1.0,
+            wet_band: 1.2,
+            shallow_width: 2.0,
+        }
+    }
+}
+
+/// The sand's surface.
+///
+/// The terrain is a regular grid of `step` sized cells, each split in two
+/// triangles along its diagonal. The triangle below some point is found
+/// straight from the cell the point's in.
+///
+/// The sand spans `[-length / 2, length / 2]` on x and
+/// `[-width / 2, width / 2]` on z, matching the old sand plane.
+/// With a `Shoreline`, the grid grows to also cover the shallow water.
+///
+/// The sand is dry, except where a `MaterialZone` says otherwise.
+/// When zones overlap, the last one wins.
+#[derive(Resource, Clone)]
+pub struct Terrain {
+    source: TerrainSource,
+    length: f32,
+    width: f32,
+    shoreline: Option<Shoreline>,
+    // grid
+    grid_length: f32,
+    grid_width: f32,
+    step: f32,
+    cols: usize,
+    rows: usize,
+    heights: Vec<f32>,
+    triangles: Vec<Triangle>, // two per cell, row by row
+    material_zones: Vec<MaterialZone>,
+}
+
+impl Terrain {
+    /// How many vertices per unit.
+    const RESOLUTION: f32 = 4.0;
+    const RAY_MAX_DISTANCE: f32 = 30.0;
+    /// Height of the water's surface.
+    pub const WATER_LEVEL: f32 = 0.0;
+    /// Sand sits this much over its heights, so it doesn't
+    /// z-fight with the water where it's flat.
+    pub const SAND_LIFT: f32 = 0.01;
+
+    pub fn new(source: TerrainSource, length: f32, width: f32) -> Self {
+        let mut terrain = Terrain {
+            source,
+            length,
+            width,
+            shoreline: None,
+            grid_length: length,
+            grid_width: width,
+            step: 1.0 / Self::RESOLUTION,
+            cols: 0,
+            rows: 0,
+            heights: Vec::new(),
+            triangles: Vec::new(),
+            material_zones: Vec::new(),
+        };
+        terrain.build();
+        terrain
+    }
+
+    /// Sets the shoreline, rebuilding the terrain.
+    pub fn with_shoreline(mut self, shoreline: Option<Shoreline>) -> Self {
+        self.shoreline = shoreline;
+        self.build();
+        self
+    }
+
+    /// Computes every vertex's height, then the triangles.
+    fn build(&mut self) {
+        let margin = self
+            .shoreline
+            .map_or(0.0, |s| s.shallow_width + s.irregularity);
+        self.grid_length = self.length + 2.0 * margin;
+        self.grid_width = self.width + 2.0 * margin;
+        self.cols = (self.grid_length * Self::RESOLUTION).ceil() as usize + 1;
+        self.rows = (self.grid_width * Self::RESOLUTION).ceil() as usize + 1;
+
+        let heightmap = match self.source {
+            TerrainSource::Heightmap(path, _) => Some(
+                image::open(format!("assets/{}", path))
+                    .expect("Could not load heightmap.")
+                    .to_luma8(),
+            ),
+            _ => None,
+        };
+
+        self.heights = Vec::with_capacity(self.cols * self.rows);
+        for j in 0..self.rows {
+            for i in 0..self.cols {
+                let x = i as f32 * self.step - self.grid_length / 2.0;
+                let z = j as f32 * self.step - self.grid_width / 2.0;
+
+                let height = match self.source {
+                    TerrainSource::Flat => 0.0,
+                    TerrainSource::Heightmap(_, max_height) => {
+                        let img = heightmap.as_ref().unwrap();
+                        let u = i as f32 / (self.cols - 1) as f32;
+                        let v = j as f32 / (self.rows - 1) as f32;
+                        let px = ((img.width() - 1) as f32 * u).round() as u32;
+                        let py = ((img.height() - 1) as f32 * v).round() as u32;
+                        img.get_pixel(px, py).0[0] as f32 / 255.0 * max_height
+                    }
+                    TerrainSource::Noise {
+                        seed,
+                        amplitude,
+                        frequency,
+                    } => fractal_noise(seed, x * frequency, z * frequency) * amplitude,
+                };
+
+                self.heights.push(match self.shoreline {
+                    None => height,
+                    Some(shoreline) => {
+                        let d = self.shore_distance(x, z);
+                        if d < 0.0 {
+                            // under water
+                            d * Shoreline::SHALLOW_SLOPE
+                        } else {
+                            // flatten the sand towards the water
+                            height * (d / shoreline.wet_band).min(1.0)
+                        }
+                    }
+                });
+            }
+        }
+
+        self.build_triangles();
+    }
+
+    /// Distance from (x, z) to the water's edge. It's negative in the water.
+    ///
+    /// Without a shoreline, the water's edge is the sand rectangle's border.
+    pub fn shore_distance(&self, x: f32, z: f32) -> f32 {
+        let d = (self.length / 2.0 - x.abs()).min(self.width / 2.0 - z.abs());
+
+        match self.shoreline {
+            None => d,
+            Some(shoreline) => {
+                let f = Shoreline::NOISE_FREQUENCY;
+                let noise = fractal_noise(shoreline.seed, x * f, z * f) * 2.0 - 1.0;
+                d + noise * shoreline.irregularity
+            }
+        }
+    }
+
+    /// Checks whether (x, z) is in the water.
+    pub fn is_water(&self, x: f32, z: f32) -> bool {
+        self.shore_distance(x, z) < 0.0
+    }
+
+    /// Returns how far along `ray` it reaches the water's surface, if it
+    /// does (over the water, within its max distance).
+    pub fn water_hit(&self, ray: &Ray) -> Option<f32> {
+        if ray.dir.y == 0.0 {
+            return None;
+        }
+
+        let t = (Self::WATER_LEVEL - ray.org.y) / ray.dir.y;
+        if t < 0.0 || t > ray.max_dist {
+            return None;
+        }
+
+        let p = ray.org + ray.dir * t;
+        self.is_water(p.x, p.z).then_some(t)
+    }
+
+    /// Sets the ground material zones.
+    pub fn with_materials(mut self, material_zones: Vec<MaterialZone>) -> Self {
+        self.material_zones = material_zones;
+        self
+    }
+
+    /// Returns the ground material at (x, z).
+    pub fn material_at(&self, x: f32, z: f32) -> GroundMaterial {
+        self.material_zones
+            .iter()
+            .rev()
+            .find(|zone| zone.contains(x, z))
+            .map_or_else(
+                || match self.shoreline {
+                    Some(shoreline) if self.shore_distance(x, z) < shoreline.wet_band => {
+                        GroundMaterial::WetSand
+                    }
+                    _ => GroundMaterial::DrySand,
+                },
+                |zone| zone.material,
+            )
+    }
+
+    /// Position of the vertex at column `i`, row `j`.
+    fn vertex(&self, i: usize, j: usize) -> Vec3 {
+        Vec3::new(
+            i as f32 * self.step - self.grid_length / 2.0,
+            self.heights[j * self.cols + i] + Self::SAND_LIFT,
+            j as f32 * self.step - self.grid_width / 2.0,
+        )
+    }
+
+    /// Splits every grid cell in two triangles.
+    fn build_triangles(&mut self) {
+        self.triangles = Vec::with_capacity(2 * (self.cols - 1) * (self.rows - 1));
+        for j in 0..self.rows - 1 {
+            for i in 0..self.cols - 1 {
+                let a = self.vertex(i, j);
+                let b = self.vertex(i + 1, j);
+                let c = self.vertex(i, j + 1);
+                let d = self.vertex(i + 1, j + 1);
+
+                self.triangles.push(Triangle { a, b: c, c: b });
+                self.triangles.push(Triangle { a: b, b: c, c: d });
+            }
+        }
+    }
+
+    /// Returns the cell (column, row) the point (x, z) is in, and where in
+    /// it (0 to 1 on each axis). Returns None if it's out of the terrain.
+    fn cell_at(&self, x: f32, z: f32) -> Option<((usize, usize), Vec2)> {
+        let fx = (x + self.grid_length / 2.0) / self.step;
+        let fz = (z + self.grid_width / 2.0) / self.step;
+        if !(0.0..=(self.cols - 1) as f32).contains(&fx)
+            || !(0.0..=(self.rows - 1) as f32).contains(&fz)
+        {
+            return None;
+        }
+
+        // the far border belongs to the last cell
+        let i = (fx.floor() as usize).min(self.cols - 2);
+        let j = (fz.floor() as usize).min(self.rows - 2);
+        Some(((i, j), Vec2::new(fx - i as f32, fz - j as f32)))
+    }
+
+    /// The two triangles of cell (i, j).
+    fn cell_triangles(&self, i: usize, j: usize) -> &[Triangle] {
+        let first = 2 * (j * (self.cols - 1) + i);
+        &self.triangles[first..first + 2]
+    }
+
+    /// Returns the triangle below (or above) the point (x, z).
+    /// Returns None if the point is out of the terrain.
+    pub fn triangle_at(&self, x: f32, z: f32) -> Option<Triangle> {
+        let ((i, j), local) = self.cell_at(x, z)?;
+
+        // the first triangle is on the cell's origin side of the diagonal
+        Some(self.cell_triangles(i, j)[(local.x + local.y > 1.0) as usize])
+    }
+
+    /// Returns the terrain's height at (x, z).
+    /// Out of the terrain, the height is 0 (water level).
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        match self.triangle_at(x, z) {
+            None => 0.0,
+            Some(triangle) => {
+                let line = Line {
+                    org: Vec3::new(x, 0.0, z),
+                    dir: Vec3::Y,
+                };
+                get_collision_point(line, triangle).map_or(0.0, |p| p.y)
+            }
+        }
+    }
+
+    /// Returns the terrain's normal at (x, z).
+    pub fn normal_at(&self, x: f32, z: f32) -> Vec3 {
+        match self.triangle_at(x, z) {
+            None => Vec3::Y,
+            Some(triangle) => {
+                let normal = Plane::from_triangle(triangle).normal;
+                if normal.y < 0.0 {
+                    -normal
+                } else {
+                    normal
+                }
+            }
+        }
+    }
+
+    /// Casts a ray from `org` towards `dir` and returns the first point
+    /// where it reaches the ground, if any.
+    ///
+    /// Only the cells the ray crosses are tested. Out of the terrain, the
+    /// ground is at water level.
+    pub fn raycast(&self, org: Vec3, dir: Vec3) -> Option<Vec3> {
+        if org.y < self.height_at(org.x, org.z) {
+            return Some(org);
+        }
+        if dir == Vec3::ZERO {
+            return None;
+        }
+
+        let ray = Ray::new(org, dir).with_max_distance(Self::RAY_MAX_DISTANCE);
+        // the part of the ray over the grid
+        let min = Vec2::new(-self.grid_length, -self.grid_width) / 2.0;
+        let max = min + Vec2::new((self.cols - 1) as f32, (self.rows - 1) as f32) * self.step;
+        let (mut t0, mut t1) = (0.0_f32, Self::RAY_MAX_DISTANCE);
+        for (o, d, lo, hi) in [
+            (org.x, ray.dir.x, min.x, max.x),
+            (org.z, ray.dir.z, min.y, max.y),
+        ] {
+            if d == 0.0 {
+                if o < lo || o > hi {
+                    t1 = -1.0;
+                }
+                continue;
+            }
+            let (a, b) = ((lo - o) / d, (hi - o) / d);
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+
+        if t0 <= t1 {
+            if let Some(t) = self.cast_cells(&ray, t0, t1) {
+                return Some(org + ray.dir * t);
+            }
+        }
+
+        (ray.dir.y < 0.0)
+            .then(|| (Self::WATER_LEVEL - org.y) / ray.dir.y)
+            .filter(|t| *t <= Self::RAY_MAX_DISTANCE)
+            .map(|t| org + ray.dir * t)
+            .filter(|p| self.cell_at(p.x, p.z).is_none())
+    }
+
+    /// Walks the cells `ray` crosses from `t0` to `t1` (a grid traversal),
+    /// returns the distance to the first triangle it hits.
+    fn cast_cells(&self, ray: &Ray, t0: f32, t1: f32) -> Option<f32> {
+        let start = ray.org + ray.dir * t0;
+        // (current cell, step, distance to next cell boundary, distance between boundaries)
+        let axis = |start: f32, org: f32, dir: f32, grid_org: f32, cells: usize| {
+            let c = (((start - grid_org) / self.step).floor().max(0.0) as usize).min(cells - 1);
+            if dir == 0.0 {
+                return (c as i32, 0, f32::INFINITY, f32::INFINITY);
+            }
+
+            let step = dir.signum() as i32;
+            let boundary = grid_org + (c + (step > 0) as usize) as f32 * self.step;
+            (
+                c as i32,
+                step,
+                (boundary - org) / dir,
+                self.step / dir.abs(),
+            )
+        };
+
+        let (mut cx, step_x, mut t_max_x, t_delta_x) = axis(
+            start.x,
+            ray.org.x,
+            ray.dir.x,
+            -self.grid_length / 2.0,
+            self.cols - 1,
+        );
+        let (mut cz, step_z, mut t_max_z, t_delta_z) = axis(
+            start.z,
+            ray.org.z,
+            ray.dir.z,
+            -self.grid_width / 2.0,
+            self.rows - 1,
+        );
+
+        loop {
+            // a cell's hits are within the cell, so the first one found is the closest
+            let hit = self
+                .cell_triangles(cx as usize, cz as usize)
+                .iter()
+                .filter_map(|triangle| ray.cast_triangle(triangle))
+                .reduce(f32::min);
+            if hit.is_some() {
+                return hit;
+            }
+
+            let t_next = t_max_x.min(t_max_z);
+            if !t_next.is_finite() || t_next > t1 {
+                return None;
+            }
+            if t_max_x < t_max_z {
+                cx += step_x;
+                t_max_x += t_delta_x;
+            } else {
+                cz += step_z;
+                t_max_z += t_delta_z;
+            }
+
+            if cx < 0 || cz < 0 || cx as usize >= self.cols - 1 || cz as usize >= self.rows - 1 {
+                return None;
+            }
+        }
+    }
+}
+
+impl From<&Terrain> for Mesh {
+    fn from(terrain: &Terrain) -> Self {
+        let mut positions = Vec::with_capacity(terrain.cols * terrain.rows);
+        let mut normals = Vec::with_capacity(terrain.cols * terrain.rows);
+        let mut uvs = Vec::with_capacity(terrain.cols * terrain.rows);
+        let mut colors = Vec::with_capacity(terrain.cols * terrain.rows);
+
+        for j in 0..terrain.rows {
+            for i in 0..terrain.cols {
+                let v = terrain.vertex(i, j);
+                // central differences
+                let hl = terrain.vertex(i.saturating_sub(1), j).y;
+                let hr = terrain.vertex((i + 1).min(terrain.cols - 1), j).y;
+                let hd = terrain.vertex(i, j.saturating_sub(1)).y;
+                let hu = terrain.vertex(i, (j + 1).min(terrain.rows - 1)).y;
+
+                positions.push(v.to_array());
+                colors.push(terrain.material_at(v.x, v.z).tint().as_linear_rgba_f32());
+                normals.push(
+                    Vec3::new(hl - hr, 2.0 * terrain.step, hd - hu)
+                        .normalize()
+                        .to_array(),
+                );
+                uvs.push([
+                    i as f32 / (terrain.cols - 1) as f32,
+                    j as f32 / (terrain.rows - 1) as f32,
+                ]);
+            }
+        }
+
+        let mut indices = Vec::with_capacity((terrain.cols - 1) * (terrain.rows - 1) * 6);
+        for j in 0..terrain.rows as u32 - 1 {
+            for i in 0..terrain.cols as u32 - 1 {
+                let a = j * terrain.cols as u32 + i;
+                let b = a + 1;
+                let c = a + terrain.cols as u32;
+                let d = c + 1;
+                indices.extend_from_slice(&[a, c, b, b, c, d]);
+            }
+        }
+
+        Mesh::new(PrimitiveTopology::TriangleList)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+            .with_indices(Some(Indices::U32(indices)))
+    }
+}
+
+/// Pseudo random value in [0, 1) for the lattice point (x, z).
+fn lattice_value(seed: u32, x: i32, z: i32) -> f32 {
+    let mut h = seed
+        .wrapping_add((x as u32).wrapping_mul(374_761_393))
+        .wrapping_add((z as u32).wrapping_mul(668_265_263));
+    h = (h ^ (h >> 13)).wrapping_mul(1_274_126_177);
+    h ^= h >> 16;
+    (h & 0x00FF_FFFF) as f32 / 0x0100_0000 as f32
+}
+
+/// Smoothly interpolated value noise, in [0, 1).
+fn value_noise(seed: u32, x: f32, z: f32) -> f32 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let (fx, fz) = (x - x0, z - z0);
+    let (sx, sz) = (fx * fx * (3.0 - 2.0 * fx), fz * fz * (3.0 - 2.0 * fz));
+    let (x0, z0) = (x0 as i32, z0 as i32);
+
+    let a = lattice_value(seed, x0, z0);
+    let b = lattice_value(seed, x0 + 1, z0);
+    let c = lattice_value(seed, x0, z0 + 1);
+    let d = lattice_value(seed, x0 + 1, z0 + 1);
+
+    let top = a + (b - a) * sx;
+    let bottom = c + (d - c) * sx;
+    top + (bottom - top) * sz
+}
+
+/// A few octaves of value noise, normalized to [0, 1).
+fn fractal_noise(seed: u32, x: f32, z: f32) -> f32 {
+    const OCTAVES: u32 = 3;
+
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut max = 0.0;
+    for octave in 0..OCTAVES {
+        let f = (1 << octave) as f32;
+        total += value_noise(seed.wrapping_add(octave), x * f, z * f) * amplitude;
+        max += amplitude;
+        amplitude /= 2.0;
+    }
+
+    total / max
+}
+
+#[cfg(test)]
+mod terrain_tests {
+    use super::*;
+
+    fn noise() -> TerrainSource {
+        TerrainSource::Noise {
+            seed: 7,
+            amplitude: 0.5,
+            frequency: 0.4,
+        }
+    }
+
+    #[test]
+    fn test_flat_terrain_height_is_sand_lift() {
+        let terrain = Terrain::new(TerrainSource::Flat, 10.0, 6.0);
+
+        assert_eq!(terrain.height_at(1.3, -2.1), Terrain::SAND_LIFT);
+        assert_eq!(terrain.normal_at(1.3, -2.1), Vec3::Y);
+    }
+
+    #[test]
+    fn test_water_hit_only_over_water() {
+        let terrain = Terrain::new(TerrainSource::Flat, 10.0, 6.0);
+        let down = |x: f32| Ray::new(Vec3::new(x, 1.0, 0.0), Vec3::NEG_Y);
+
+        assert_eq!(terrain.water_hit(&down(6.0)), Some(1.0));
+        assert_eq!(terrain.water_hit(&down(0.0)), None);
+        assert_eq!(terrain.water_hit(&down(6.0).with_max_distance(0.5)), None);
+    }
+
+    #[test]
+    fn test_triangle_at_out_of_terrain() {
+        let terrain = Terrain::new(TerrainSource::Flat, 10.0, 6.0);
+
+        assert!(terrain.triangle_at(6.0, 0.0).is_none());
+        assert_eq!(terrain.height_at(6.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_noise_terrain_height_within_amplitude() {
+        let terrain = Terrain::new(noise(), 10.0, 6.0);
+
+        for i in -9..9 {
+            let h = terrain.height_at(i as f32 * 0.5, i as f32 * 0.3);
+            assert!(
+                (0.0..=0.5).contains(&(h - Terrain::SAND_LIFT)),
+                "height {} out of range",
+                h
+            );
+        }
+    }
+
+    #[test]
+    fn test_height_matches_grid_vertices() {
+        let terrain = Terrain::new(noise(), 10.0, 6.0);
+        let v = terrain.vertex(7, 5);
+
+        assert!((terrain.height_at(v.x, v.z) - v.y).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_material_outside_zones_is_dry_sand() {
+        let terrain =
+            Terrain::new(TerrainSource::Flat, 10.0, 6.0).with_materials(vec![MaterialZone::new(
+                GroundMaterial::WetSand,
+                2.0,
+                0.0,
+                1.0,
+            )]);
+
+        assert_eq!(terrain.material_at(-2.0, 0.0), GroundMaterial::DrySand);
+        assert_eq!(terrain.material_at(2.5, 0.5), GroundMaterial::WetSand);
+    }
+
+    #[test]
+    fn test_last_material_zone_wins() {
+        let terrain = Terrain::new(TerrainSource::Flat, 10.0, 6.0).with_materials(vec![
+            MaterialZone::new(GroundMaterial::WetSand, 0.0, 0.0, 2.0),
+            MaterialZone::new(GroundMaterial::Pebbles, 0.5, 0.0, 1.0),
+        ]);
+
+        assert_eq!(terrain.material_at(0.5, 0.0), GroundMaterial::Pebbles);
+        assert_eq!(terrain.material_at(-1.5, 0.0), GroundMaterial::WetSand);
+    }
+
+    #[test]
+    fn test_dry_sand_has_full_traction() {
+        assert!((GroundMaterial::DrySand.traction() - 1.0).abs() < 0.0001);
+        assert!(GroundMaterial::WetSand.traction() < 1.0);
+    }
+
+    #[test]
+    fn test_water_without_shoreline_is_out_of_sand() {
+        let terrain = Terrain::new(TerrainSource::Flat, 10.0, 6.0);
+
+        assert!(!terrain.is_water(4.9, 2.9));
+        assert!(terrain.is_water(5.1, 0.0));
+        assert!(terrain.is_water(0.0, -3.1));
+    }
+
+    #[test]
+    fn test_shoreline_has_wet_band_and_shallow_water() {
+        let shoreline = Shoreline::default();
+        let terrain = Terrain::new(TerrainSource::Flat, 10.0, 6.0).with_shoreline(Some(shoreline));
+
+        // walk from the center towards the water
+        let mut x = 0.0;
+        while !terrain.is_water(x, 0.0) {
+            x += 0.05;
+        }
+
+        assert!(terrain.height_at(x + 0.5, 0.0) < 0.0);
+        assert_eq!(terrain.material_at(x - 0.1, 0.0), GroundMaterial::WetSand);
+        assert_eq!(terrain.material_at(0.0, 0.0), GroundMaterial::DrySand);
+    }
+
+    #[test]
+    fn test_triangle_at_contains_point() {
+        let terrain = Terrain::new(noise(), 10.0, 6.0);
+        let flat = |t: Triangle| Triangle {
+            a: t.a * Vec3::new(1.0, 0.0, 1.0),
+            b: t.b * Vec3::new(1.0, 0.0, 1.0),
+            c: t.c * Vec3::new(1.0, 0.0, 1.0),
+        };
+
+        for i in 0..200 {
+            let (x, z) = (
+                (i as f32 * 0.731) % 10.0 - 5.0,
+                (i as f32 * 0.377) % 6.0 - 3.0,
+            );
+            let triangle = terrain.triangle_at(x, z).unwrap();
+
+            assert!(point_inside_triangle(Vec3::new(x, 0.0, z), flat(triangle)));
+        }
+        // the far border is still in
+        assert!(terrain.triangle_at(5.0, 3.0).is_some());
+    }
+
+    #[test]
+    fn test_raycast_from_out_of_the_terrain() {
+        let terrain = Terrain::new(noise(), 10.0, 6.0);
+        let hit = terrain
+            .raycast(Vec3::new(-8.0, 3.0, 0.0), Vec3::new(1.0, -0.4, 0.1))
+            .unwrap();
+
+        assert!(hit.x > -5.0);
+        assert!((hit.y - terrain.height_at(hit.x, hit.z)).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_raycast_leaving_the_terrain_lands_on_water_level() {
+        let terrain = Terrain::new(TerrainSource::Flat, 10.0, 6.0);
+        let hit = terrain
+            .raycast(Vec3::new(4.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0))
+            .map(|hit| hit.y);
+        assert_eq!(hit, None);
+
+        let hit = terrain
+            .raycast(Vec3::new(4.5, 1.0, 0.0), Vec3::new(1.0, -0.5, 0.0))
+            .unwrap();
+        assert!((hit - Vec3::new(6.5, 0.0, 0.0)).length() < 0.001);
+    }
+
+    #[test]
+    fn test_raycast_flat_terrain() {
+        let terrain = Terrain::new(TerrainSource::Flat, 10.0, 6.0);
+        let hit = terrain
+            .raycast(Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0))
+            .unwrap();
+
+        let lift = Terrain::SAND_LIFT;
+        assert!((hit - Vec3::new(1.0 - lift, lift, 0.0)).length() < 0.001);
+    }
+
+    #[test]
+    fn test_raycast_pointing_up_misses() {
+        let terrain = Terrain::new(noise(), 10.0, 6.0);
+
+        assert!(terrain.raycast(Vec3::new(0.0, 1.0, 0.0), Vec3::Y).is_none());
+    }
+
+    #[test]
+    fn test_raycast_lands_on_ground() {
+        let terrain = Terrain::new(noise(), 10.0, 6.0);
+        let hit = terrain
+            .raycast(Vec3::new(-2.0, 2.0, 0.5), Vec3::new(1.0, -0.8, 0.2))
+            .unwrap();
+
+        assert!((hit.y - terrain.height_at(hit.x, hit.z)).abs() < 0.001);
+    }
+}